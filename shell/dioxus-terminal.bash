@@ -0,0 +1,66 @@
+# dioxus-terminal shell integration for bash
+#
+# Loaded with `bash --rcfile`; sources the user's ~/.bashrc first, then
+# reports prompts (OSC 133) and the working directory (OSC 7).
+
+if [ -f ~/.bashrc ]; then
+    . ~/.bashrc
+fi
+
+if [ -n "$__dioxus_terminal_loaded" ]; then
+    return
+fi
+__dioxus_terminal_loaded=1
+
+__dioxus_terminal_cwd() {
+    local dir="${PWD//\%/%25}"
+    printf '\e]7;file://%s%s\a' "$HOSTNAME" "${dir// /%20}"
+}
+
+__dioxus_terminal_prompt() {
+    local status=$?
+    __dioxus_terminal_ready=
+    if [ -n "$__dioxus_terminal_running" ]; then
+        printf '\e]133;D;%s\a' "$status"
+    fi
+    __dioxus_terminal_running=
+    __dioxus_terminal_cwd
+    return $status
+}
+
+# Runs after the rest of PROMPT_COMMAND, so the user's prompt commands
+# (starship, direnv, `history -a`) aren't taken for command output
+__dioxus_terminal_prompt_done() {
+    __dioxus_terminal_ready=1
+}
+
+__dioxus_terminal_preexec() {
+    # Only the first command after a prompt starts its output
+    if [ -z "$__dioxus_terminal_ready" ] || [ "$BASH_COMMAND" = __dioxus_terminal_prompt ]; then
+        return
+    fi
+    __dioxus_terminal_ready=
+    __dioxus_terminal_running=1
+    printf '\e]133;C\a'
+}
+
+if [ -n "${bash_preexec_imported:-}${__bp_imported:-}" ]; then
+    # bash-preexec owns the DEBUG trap and PROMPT_COMMAND; use its hooks
+    precmd_functions+=(__dioxus_terminal_prompt __dioxus_terminal_prompt_done)
+    preexec_functions+=(__dioxus_terminal_preexec)
+else
+    # Keep running any DEBUG trap set by the user's bashrc
+    __dioxus_terminal_debug_trap=$(trap -p DEBUG)
+    __dioxus_terminal_debug_trap=${__dioxus_terminal_debug_trap#"trap -- '"}
+    __dioxus_terminal_debug_trap=${__dioxus_terminal_debug_trap%"' DEBUG"}
+    __dioxus_terminal_debug_trap=${__dioxus_terminal_debug_trap//"'\''"/"'"}
+    trap '__dioxus_terminal_preexec; eval "$__dioxus_terminal_debug_trap"' DEBUG
+
+    # PROMPT_COMMAND is an array in bash 5.1+ if the user made it one
+    if [[ "$(declare -p PROMPT_COMMAND 2>/dev/null)" == "declare -a"* ]]; then
+        PROMPT_COMMAND=(__dioxus_terminal_prompt "${PROMPT_COMMAND[@]}" __dioxus_terminal_prompt_done)
+    else
+        PROMPT_COMMAND="__dioxus_terminal_prompt"$'\n'"${PROMPT_COMMAND:-}"$'\n'"__dioxus_terminal_prompt_done"
+    fi
+fi
+PS1="\[\e]133;A\a\]$PS1\[\e]133;B\a\]"
@@ -0,0 +1,29 @@
+# dioxus-terminal shell integration for fish
+#
+# Loaded with `fish --init-command`; reports prompts (OSC 133) and the
+# working directory (OSC 7).
+
+if set -q __dioxus_terminal_loaded
+    exit
+end
+set -g __dioxus_terminal_loaded 1
+
+function __dioxus_terminal_prompt --on-event fish_prompt
+    printf '\e]7;file://%s%s\a' $hostname (string replace -a ' ' '%20' -- (string replace -a '%' '%25' -- $PWD))
+    printf '\e]133;A\a'
+end
+
+function __dioxus_terminal_preexec --on-event fish_preexec
+    printf '\e]133;C\a'
+end
+
+function __dioxus_terminal_postexec --on-event fish_postexec
+    printf '\e]133;D;%s\a' $status
+end
+
+# Mark where the command starts, after the user's prompt
+functions -c fish_prompt __dioxus_terminal_user_prompt
+function fish_prompt
+    __dioxus_terminal_user_prompt
+    printf '\e]133;B\a'
+end
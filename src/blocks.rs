@@ -0,0 +1,260 @@
+//! Command blocks built from shell integration marks (OSC 133)
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::term::Grid;
+
+/// Most command blocks kept (older ones are dropped)
+const MAX_BLOCKS: usize = 1000;
+
+/// A prompt, the command typed at it, and the command's output
+///
+/// Lines are absolute: they count lines scrolled off the top of the grid, so
+/// they stay valid as output scrolls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandBlock {
+    /// Line the prompt starts on
+    pub prompt_line: usize,
+    /// Command text as echoed by the shell
+    pub command: String,
+    /// Lines of output, once the command has started
+    pub output: Option<Range<usize>>,
+    /// Exit code, if the shell reported one
+    pub exit_code: Option<i32>,
+    /// Time from submitting the command to the shell reporting its end
+    pub duration: Option<Duration>,
+    /// Whether the command has finished
+    pub finished: bool,
+    started: Option<Instant>,
+}
+
+impl CommandBlock {
+    fn new(prompt_line: usize) -> Self {
+        Self {
+            prompt_line,
+            command: String::new(),
+            output: None,
+            exit_code: None,
+            duration: None,
+            finished: false,
+            started: None,
+        }
+    }
+
+    /// Block for a command run without shell integration, reported when it exits
+    pub(crate) fn exited(command: &str, exit_code: Option<i32>, duration: Duration) -> Self {
+        Self {
+            command: command.to_string(),
+            exit_code,
+            duration: Some(duration),
+            finished: true,
+            ..Self::new(0)
+        }
+    }
+
+    /// Whether the command failed (finished with a non-zero exit code)
+    #[must_use]
+    pub fn failed(&self) -> bool {
+        self.exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+/// Tracks command blocks as OSC 133 marks arrive
+#[derive(Debug, Default)]
+pub(crate) struct CommandTracker {
+    blocks: Vec<CommandBlock>,
+    // Where the command text starts, as (absolute line, column)
+    command_start: Option<(usize, usize)>,
+}
+
+impl CommandTracker {
+    /// All blocks, oldest first
+    pub fn blocks(&self) -> &[CommandBlock] {
+        &self.blocks
+    }
+
+    /// `A`: a new prompt starts at `line`
+    pub fn prompt_start(&mut self, line: usize) {
+        // A prompt without `D` means the previous command ended silently
+        if let Some(block) = self.blocks.last_mut() {
+            if block.output.is_some() && !block.finished {
+                finish(block, line, None);
+            }
+        }
+        if self.blocks.len() >= MAX_BLOCKS {
+            self.blocks.remove(0);
+        }
+        self.blocks.push(CommandBlock::new(line));
+        self.command_start = None;
+    }
+
+    /// `B`: the user starts typing at `(line, col)`
+    pub fn command_start(&mut self, line: usize, col: usize) {
+        if self.blocks.is_empty() {
+            self.blocks.push(CommandBlock::new(line));
+        }
+        self.command_start = Some((line, col));
+    }
+
+    /// `C`: the command was submitted and output starts at `line`
+    ///
+    /// The command text is read from the grid, between the `B` mark and `end`
+    /// (the cursor, as an absolute line and column).
+    pub fn output_start(&mut self, grid: &Grid, scrolled: usize, end: (usize, usize)) {
+        let command = self
+            .command_start
+            .take()
+            .map(|start| grid_text(grid, scrolled, start, end))
+            .unwrap_or_default();
+        let Some(block) = self.blocks.last_mut() else {
+            return;
+        };
+        block.command = command.trim().to_string();
+        // Output starts on the line after the command, or the cursor line
+        let line = if end.1 == 0 { end.0 } else { end.0 + 1 };
+        block.output = Some(line..line);
+        block.started = Some(Instant::now());
+    }
+
+    /// `D`: the command finished; output ends before `(line, col)`
+    ///
+    /// Returns the finished block, unless it had already ended.
+    pub fn command_end(
+        &mut self,
+        line: usize,
+        col: usize,
+        exit_code: Option<i32>,
+    ) -> Option<CommandBlock> {
+        let block = self.blocks.last_mut().filter(|block| !block.finished)?;
+        let end = if col == 0 { line } else { line + 1 };
+        finish(block, end, exit_code);
+        Some(block.clone())
+    }
+
+    /// The child exited while a command was running; output ends before `(line, col)`
+    ///
+    /// Returns the block, without an exit code, if a command's output had started.
+    pub fn command_exited(&mut self, line: usize, col: usize) -> Option<CommandBlock> {
+        let running = self
+            .blocks
+            .last()
+            .is_some_and(|block| block.output.is_some() && !block.finished);
+        if running {
+            self.command_end(line, col, None)
+        } else {
+            None
+        }
+    }
+}
+
+/// Mark a block finished with output ending before line `end`
+fn finish(block: &mut CommandBlock, end: usize, exit_code: Option<i32>) {
+    if let Some(output) = &mut block.output {
+        output.end = end.max(output.start);
+    }
+    block.exit_code = exit_code;
+    block.duration = block.started.map(|started| started.elapsed());
+    block.finished = true;
+}
+
+/// Text of the grid between two absolute positions (end exclusive)
+///
+/// Soft-wrapped rows are joined; other rows end with a newline. Positions
+/// scrolled off the grid are skipped.
+pub(crate) fn grid_text(
+    grid: &Grid,
+    scrolled: usize,
+    start: (usize, usize),
+    end: (usize, usize),
+) -> String {
+    let mut text = String::new();
+    let first = start.0.max(scrolled);
+    for line in first..=end.0 {
+        let Some(row) = line.checked_sub(scrolled).filter(|&row| row < grid.rows()) else {
+            break;
+        };
+        let from = if line == start.0 { start.1 } else { 0 };
+        let to = if line == end.0 { end.1 } else { grid.cols() };
+        let chunk: String = (from..to)
+            .filter_map(|col| grid.get(row, col).map(|cell| cell.c))
+            .collect();
+        if grid.is_wrapped(row) && line != end.0 {
+            text.push_str(&chunk);
+        } else {
+            text.push_str(chunk.trim_end());
+            if line != end.0 {
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+/// Text of whole lines `range` (absolute), without trailing blank lines
+pub(crate) fn lines_text(grid: &Grid, scrolled: usize, range: &Range<usize>) -> String {
+    if range.is_empty() {
+        return String::new();
+    }
+    let text = grid_text(grid, scrolled, (range.start, 0), (range.end, 0));
+    text.trim_end_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::Cell;
+
+    fn grid_with(lines: &[&str], cols: usize) -> Grid {
+        let mut grid = Grid::new(lines.len(), cols);
+        for (row, line) in lines.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                grid.set(row, col, Cell::new(c));
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_block_lifecycle() {
+        let grid = grid_with(&["$ ls -l", "a.txt", "b.txt", "$ "], 10);
+        let mut tracker = CommandTracker::default();
+        tracker.prompt_start(0);
+        tracker.command_start(0, 2);
+        tracker.output_start(&grid, 0, (1, 0));
+        tracker.command_end(3, 0, Some(0));
+        let block = &tracker.blocks()[0];
+        assert_eq!(block.prompt_line, 0);
+        assert_eq!(block.command, "ls -l");
+        assert_eq!(block.output, Some(1..3));
+        assert_eq!(block.exit_code, Some(0));
+        assert!(block.finished);
+        assert!(!block.failed());
+        assert!(block.duration.is_some());
+        assert_eq!(lines_text(&grid, 0, &(1..3)), "a.txt\nb.txt");
+    }
+
+    #[test]
+    fn test_prompt_ends_unfinished_block() {
+        let grid = grid_with(&["$ false", "$ "], 10);
+        let mut tracker = CommandTracker::default();
+        tracker.prompt_start(0);
+        tracker.command_start(0, 2);
+        tracker.output_start(&grid, 0, (1, 0));
+        tracker.prompt_start(1);
+        assert_eq!(tracker.blocks().len(), 2);
+        assert!(tracker.blocks()[0].finished);
+        assert_eq!(tracker.blocks()[0].exit_code, None);
+        tracker.command_end(1, 2, Some(1));
+        assert!(tracker.blocks()[1].failed());
+    }
+
+    #[test]
+    fn test_grid_text_scrolled() {
+        let mut grid = grid_with(&["abcd", "ef", "gh"], 4);
+        grid.set_wrapped(0, true);
+        // Two lines have scrolled off, so line 2 is row 0
+        assert_eq!(grid_text(&grid, 2, (2, 1), (4, 1)), "bcdef\ng");
+        assert_eq!(grid_text(&grid, 2, (0, 0), (2, 2)), "ab");
+    }
+}
@@ -0,0 +1,151 @@
+//! Character set designation (G0-G3) and shifts (SI/SO, SS2/SS3, LS2/LS3)
+
+/// A character set that can be designated into G0-G3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Charset {
+    /// US ASCII (`B`)
+    #[default]
+    Ascii,
+    /// DEC special graphics, used for line drawing (`0`)
+    DecSpecial,
+    /// United Kingdom: `#` is a pound sign (`A`)
+    Uk,
+}
+
+impl Charset {
+    /// Charset for a designation's final byte (unknown sets fall back to ASCII)
+    fn from_final(byte: u8) -> Self {
+        match byte {
+            b'0' => Self::DecSpecial,
+            b'A' => Self::Uk,
+            _ => Self::Ascii,
+        }
+    }
+
+    /// Map a printable ASCII character through this set
+    fn map(self, c: char) -> char {
+        match self {
+            Self::Ascii => c,
+            Self::Uk => {
+                if c == '#' {
+                    '£'
+                } else {
+                    c
+                }
+            }
+            Self::DecSpecial => dec_special(c),
+        }
+    }
+}
+
+/// DEC special graphics for `_` through `~`
+fn dec_special(c: char) -> char {
+    match c {
+        '_' => ' ',
+        '`' => '◆',
+        'a' => '▒',
+        'b' => '␉',
+        'c' => '␌',
+        'd' => '␍',
+        'e' => '␊',
+        'f' => '°',
+        'g' => '±',
+        'h' => '␤',
+        'i' => '␋',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        _ => c,
+    }
+}
+
+/// Designated sets and which one is invoked into GL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Charsets {
+    slots: [Charset; 4],
+    // Set invoked into GL (0 after SI, 1 after SO, 2/3 after LS2/LS3)
+    gl: usize,
+    // Set used for the next character only (SS2/SS3)
+    single_shift: Option<usize>,
+}
+
+impl Charsets {
+    /// Designate the set named by `final_byte` into G`slot`
+    pub fn designate(&mut self, slot: usize, final_byte: u8) {
+        if let Some(set) = self.slots.get_mut(slot) {
+            *set = Charset::from_final(final_byte);
+        }
+    }
+
+    /// Invoke G`slot` into GL (SI, SO, LS2, LS3)
+    pub fn lock_shift(&mut self, slot: usize) {
+        self.gl = slot.min(3);
+    }
+
+    /// Use G`slot` for the next character only (SS2, SS3)
+    pub fn single_shift(&mut self, slot: usize) {
+        self.single_shift = Some(slot.min(3));
+    }
+
+    /// Map a printed character through the active set
+    pub fn map(&mut self, c: char) -> char {
+        let slot = self.single_shift.take().unwrap_or(self.gl);
+        if c.is_ascii_graphic() {
+            self.slots[slot].map(c)
+        } else {
+            c
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print(charsets: &mut Charsets, text: &str) -> String {
+        text.chars().map(|c| charsets.map(c)).collect()
+    }
+
+    #[test]
+    fn test_line_drawing() {
+        let mut charsets = Charsets::default();
+        charsets.designate(0, b'0');
+        assert_eq!(print(&mut charsets, "lqqk"), "┌──┐");
+        assert_eq!(print(&mut charsets, "x ABC x"), "│ ABC │");
+        charsets.designate(0, b'B');
+        assert_eq!(print(&mut charsets, "lqqk"), "lqqk");
+    }
+
+    #[test]
+    fn test_shifts() {
+        let mut charsets = Charsets::default();
+        charsets.designate(1, b'0');
+        charsets.designate(2, b'A');
+        // SO invokes G1, SI goes back to G0
+        charsets.lock_shift(1);
+        assert_eq!(print(&mut charsets, "mj"), "└┘");
+        charsets.lock_shift(0);
+        assert_eq!(print(&mut charsets, "mj"), "mj");
+        // A single shift applies to one character
+        charsets.single_shift(2);
+        assert_eq!(print(&mut charsets, "##"), "£#");
+    }
+}
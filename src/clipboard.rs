@@ -0,0 +1,146 @@
+//! OSC 52 clipboard access and the policy that guards it
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use dioxus::prelude::Callback;
+
+/// Default limit for clipboard contents set or read through OSC 52 (1 MiB)
+pub const DEFAULT_CLIPBOARD_LIMIT: usize = 1024 * 1024;
+
+/// A clipboard access requested by the application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardRequest {
+    /// Copy text to the clipboard
+    Write(String),
+    /// Read the clipboard contents back
+    Read,
+}
+
+/// Which OSC 52 requests reach the system clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ClipboardPolicy {
+    /// Ignore all requests
+    Deny,
+    /// Allow copying, ignore reads (default)
+    #[default]
+    AllowWrite,
+    /// Allow copying and reading
+    AllowReadWrite,
+    /// Ask the callback, which returns whether to allow the request
+    Ask(Callback<ClipboardRequest, bool>),
+}
+
+impl ClipboardPolicy {
+    /// Check whether a request is allowed
+    #[must_use]
+    pub fn allows(&self, request: &ClipboardRequest) -> bool {
+        match self {
+            Self::Deny => false,
+            Self::AllowWrite => matches!(request, ClipboardRequest::Write(_)),
+            Self::AllowReadWrite => true,
+            Self::Ask(callback) => callback.call(request.clone()),
+        }
+    }
+}
+
+/// A parsed OSC 52 sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ClipboardOp {
+    /// Set the clipboard to the decoded text
+    Set(String),
+    /// Report the clipboard for the given selection parameter
+    Query { selection: String },
+}
+
+/// Parse the data of an OSC 52 sequence (`selection;base64` or `selection;?`)
+///
+/// Returns `None` for malformed sequences and text longer than `limit` bytes.
+pub(crate) fn parse_osc52(data: &str, limit: usize) -> Option<ClipboardOp> {
+    let (selection, payload) = data.split_once(';')?;
+    if payload == "?" {
+        return Some(ClipboardOp::Query {
+            selection: selection.to_string(),
+        });
+    }
+    // Reject oversized payloads before decoding them
+    if payload.len() > limit.div_ceil(3) * 4 {
+        return None;
+    }
+    let bytes = STANDARD.decode(payload).ok()?;
+    if bytes.len() > limit {
+        return None;
+    }
+    String::from_utf8(bytes).ok().map(ClipboardOp::Set)
+}
+
+/// Encode the reply to a clipboard query, truncating text longer than `limit` bytes
+pub(crate) fn osc52_reply(selection: &str, text: &str, limit: usize) -> Vec<u8> {
+    let mut end = text.len().min(limit);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "\x1b]52;{selection};{}\x1b\\",
+        STANDARD.encode(&text.as_bytes()[..end])
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_set() {
+        assert_eq!(
+            parse_osc52("c;aGVsbG8=", DEFAULT_CLIPBOARD_LIMIT),
+            Some(ClipboardOp::Set("hello".to_string()))
+        );
+        // Empty payload clears the clipboard
+        assert_eq!(
+            parse_osc52(";", DEFAULT_CLIPBOARD_LIMIT),
+            Some(ClipboardOp::Set(String::new()))
+        );
+        assert_eq!(parse_osc52("c;not base64!", DEFAULT_CLIPBOARD_LIMIT), None);
+        assert_eq!(parse_osc52("c", DEFAULT_CLIPBOARD_LIMIT), None);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_osc52("p;?", DEFAULT_CLIPBOARD_LIMIT),
+            Some(ClipboardOp::Query {
+                selection: "p".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_size_limit() {
+        assert_eq!(
+            parse_osc52("c;aGVsbG8=", 5),
+            Some(ClipboardOp::Set("hello".to_string()))
+        );
+        assert_eq!(parse_osc52("c;aGVsbG8=", 4), None);
+    }
+
+    #[test]
+    fn test_reply() {
+        assert_eq!(
+            osc52_reply("c", "hello", DEFAULT_CLIPBOARD_LIMIT),
+            b"\x1b]52;c;aGVsbG8=\x1b\\"
+        );
+        // Truncation never splits a character
+        assert_eq!(osc52_reply("c", "h\u{e9}", 2), b"\x1b]52;c;aA==\x1b\\");
+    }
+
+    #[test]
+    fn test_policy() {
+        let write = ClipboardRequest::Write("x".to_string());
+        let read = ClipboardRequest::Read;
+        assert!(!ClipboardPolicy::Deny.allows(&write));
+        assert!(ClipboardPolicy::AllowWrite.allows(&write));
+        assert!(!ClipboardPolicy::AllowWrite.allows(&read));
+        assert!(ClipboardPolicy::AllowReadWrite.allows(&read));
+    }
+}
@@ -0,0 +1,2474 @@
+//! Terminal output parser and emulator state
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::blocks::{CommandBlock, CommandTracker};
+use crate::charset::Charsets;
+use crate::clipboard::{ClipboardOp, DEFAULT_CLIPBOARD_LIMIT, parse_osc52};
+use crate::graphics::{Image, ImageStore, Placement};
+use crate::iterm2::{FileArgs, FileCommand, decode_contents, parse_osc1337};
+use crate::keyboard::KeyboardModes;
+use crate::kitty::{Action, GraphicsCommand, Transfer, decode_image};
+use crate::modes::TermModes;
+use crate::mouse::MouseModes;
+use crate::notification::{Notification, parse_osc9, parse_osc777};
+use crate::pty::Pty;
+use crate::sixel::SixelDecoder;
+use crate::term::{
+    Cell, Color, CursorShape, CursorStyle, Grid, Hyperlink, LineSize, PromptMark, Style,
+};
+
+/// Longest OSC/DCS payload kept for dispatch (the rest is discarded)
+const MAX_SEQUENCE_LEN: usize = 4096;
+
+/// Primary device attributes: VT220 with Sixel graphics and ANSI color
+const PRIMARY_DA: &str = "\x1b[?62;4;22c";
+
+/// Maximum depth of the XTWINOPS title stack
+const TITLE_STACK_LIMIT: usize = 10;
+
+/// Shortest time between two reported bells; bells in between are dropped
+const BELL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Events raised while parsing, dispatched by the widget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TermEvent {
+    /// Window title changed (OSC 0/2 or title stack pop)
+    TitleChanged(String),
+    /// Application copied text (OSC 52)
+    ClipboardWrite(String),
+    /// Application asked for the clipboard contents (OSC 52 query)
+    ClipboardRead { selection: String },
+    /// Working directory changed (OSC 7 or `/proc`)
+    CwdChanged(PathBuf),
+    /// Application asked for a desktop notification (OSC 9 / OSC 777)
+    Notification(Notification),
+    /// A command reported its end through shell integration (OSC 133 `D`)
+    CommandFinished(CommandBlock),
+    /// The application rang the bell (BEL), at most once per `BELL_INTERVAL`
+    Bell,
+}
+
+/// Escape sequence parsing state
+#[derive(Default)]
+pub(crate) enum EscapeState {
+    #[default]
+    Normal,
+    Escape,           // Just saw ESC
+    Designate(usize), // After ESC ( ) * + (or - . /): next byte names the G0-G3 set
+    LineAttribute,    // After ESC #: next byte sets the line size (or DECALN)
+    Csi,              // In CSI sequence (ESC [)
+    Osc,              // In OSC sequence (ESC ]) - consume until BEL or ST
+    OscEscape,        // In OSC, just saw ESC (looking for \)
+    Dcs,              // In DCS sequence (ESC P) - consume until ST
+    DcsEscape,        // In DCS, just saw ESC (looking for \)
+    Sixel,            // In Sixel data (DCS q) - decoded until ST
+    SixelEscape,      // In Sixel data, just saw ESC (looking for \)
+    Apc,              // In APC sequence (ESC _) - consume until ST
+    ApcEscape,        // In APC, just saw ESC (looking for \)
+}
+
+/// Cursor state saved by DECSC (`ESC 7`) or `CSI s`
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    wrap_pending: bool,
+    fg: Color,
+    bg: Color,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    charsets: Charsets,
+    origin: bool,
+}
+
+/// Terminal state shared between render and coroutine
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct TermState {
+    pub pty: Option<Pty>,
+    pub cursor_row: usize,
+    pub cursor_col: usize,
+    // A character was printed in the last column; the next one wraps first
+    wrap_pending: bool,
+    // Cursor saved by DECSC / `CSI s`
+    saved_cursor: Option<SavedCursor>,
+    // Cursor visibility (DECTCEM) and application-selected shape/blink
+    pub cursor_visible: bool,
+    pub cursor_shape: Option<CursorShape>,
+    pub cursor_blink: Option<bool>,
+    // Current text attributes
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    // Keyboard protocol negotiated by the application
+    pub keyboard: KeyboardModes,
+    // Mouse reporting requested by the application, and the last reported cell
+    pub mouse: MouseModes,
+    pub mouse_cell: Option<(usize, usize)>,
+    // Focus in/out reports requested (mode 1004)
+    pub focus_reporting: bool,
+    // Autowrap, insert, origin, reverse video and newline modes
+    pub modes: TermModes,
+    // Scrolling region set by DECSTBM as first and last row; `None` is the whole screen
+    scroll_region: Option<(usize, usize)>,
+    // Character sets designated into G0-G3 and the active shift
+    pub charsets: Charsets,
+    // Escape sequence parsing
+    pub escape_state: EscapeState,
+    pub escape_buf: Vec<u8>,
+    // Active OSC 8 hyperlink, applied to printed cells
+    pub link: Option<Arc<Hyperlink>>,
+    // Palette for indexed SGR colors, changed at runtime by OSC 4/104
+    pub palette: [Color; 256],
+    // Palette from the theme, restored by OSC 104 and RIS
+    base_palette: [Color; 256],
+    // Foreground, background and cursor colors from the theme (OSC 10/11/12 order)
+    pub theme_colors: [Color; 3],
+    // Runtime overrides of the theme colors, reset by OSC 110/111/112
+    pub color_overrides: [Option<Color>; 3],
+    // Largest clipboard payload accepted through OSC 52, in bytes
+    pub clipboard_limit: usize,
+    // Working directory from OSC 7, or from `/proc` while the shell doesn't report it
+    pub cwd: Option<PathBuf>,
+    pub cwd_reported: bool,
+    // Lines scrolled off the top, so rows can be given stable line numbers
+    pub scrolled_lines: usize,
+    // `scrolled_lines` when the screen was last published to the widget's grid
+    pub published_lines: usize,
+    // Command blocks from shell integration marks (OSC 133)
+    pub commands: CommandTracker,
+    // Inline images, and the cell size in pixels used to place them
+    pub images: ImageStore,
+    pub cell_size: (u32, u32),
+    sixel: Option<Box<SixelDecoder>>,
+    kitty_transfer: Option<Transfer>,
+    iterm_upload: Option<(FileArgs, String)>,
+    // When the last bell was reported, for rate limiting
+    last_bell: Option<Instant>,
+    // Window title and icon name (OSC 0/1/2) with the XTWINOPS stack
+    pub title: String,
+    pub icon_name: String,
+    title_stack: Vec<(String, String)>,
+    // Bytes to send back to the application (query responses)
+    replies: Vec<u8>,
+    // Events for the widget to dispatch
+    events: Vec<TermEvent>,
+}
+
+impl TermState {
+    /// Create terminal state around an (optional) PTY
+    pub fn new(pty: Option<Pty>) -> Self {
+        Self {
+            pty,
+            cursor_row: 0,
+            cursor_col: 0,
+            wrap_pending: false,
+            saved_cursor: None,
+            cursor_visible: true,
+            cursor_shape: None,
+            cursor_blink: None,
+            fg: Color::default_fg(),
+            bg: Color::default_bg(),
+            bold: false,
+            dim: false,
+            italic: false,
+            underline: false,
+            keyboard: KeyboardModes::default(),
+            mouse: MouseModes::default(),
+            mouse_cell: None,
+            focus_reporting: false,
+            modes: TermModes::default(),
+            scroll_region: None,
+            charsets: Charsets::default(),
+            escape_state: EscapeState::Normal,
+            escape_buf: Vec::new(),
+            link: None,
+            palette: default_palette(),
+            base_palette: default_palette(),
+            theme_colors: [
+                Color::default_fg(),
+                Color::default_bg(),
+                Color::default_fg(),
+            ],
+            color_overrides: [None; 3],
+            clipboard_limit: DEFAULT_CLIPBOARD_LIMIT,
+            cwd: None,
+            cwd_reported: false,
+            scrolled_lines: 0,
+            published_lines: 0,
+            commands: CommandTracker::default(),
+            images: ImageStore::default(),
+            cell_size: (8, 16),
+            sixel: None,
+            kitty_transfer: None,
+            iterm_upload: None,
+            last_bell: None,
+            title: String::new(),
+            icon_name: String::new(),
+            title_stack: Vec::new(),
+            replies: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Effective cursor style, falling back to `default` where the application didn't choose
+    pub fn cursor_style(&self, default: CursorStyle) -> CursorStyle {
+        CursorStyle::new(
+            self.cursor_shape.unwrap_or(default.shape),
+            self.cursor_blink.unwrap_or(default.blinking),
+        )
+    }
+
+    /// First and last row of the scrolling region on a grid of `rows` rows
+    fn margins(&self, rows: usize) -> (usize, usize) {
+        match self.scroll_region {
+            Some((top, bottom)) if bottom < rows => (top, bottom),
+            _ => (0, rows - 1),
+        }
+    }
+
+    /// Reset text attributes to the defaults (SGR 0)
+    fn reset_attributes(&mut self) {
+        self.fg = Color::default_fg();
+        self.bg = Color::default_bg();
+        self.bold = false;
+        self.dim = false;
+        self.italic = false;
+        self.underline = false;
+    }
+
+    /// Save the cursor position, attributes, charsets and origin mode (DECSC)
+    fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            wrap_pending: self.wrap_pending,
+            fg: self.fg,
+            bg: self.bg,
+            bold: self.bold,
+            dim: self.dim,
+            italic: self.italic,
+            underline: self.underline,
+            charsets: self.charsets,
+            origin: self.modes.origin,
+        });
+    }
+
+    /// Restore the cursor saved by DECSC (DECRC), or home it with default attributes
+    fn restore_cursor(&mut self, rows: usize, cols: usize) {
+        let Some(saved) = self.saved_cursor else {
+            self.cursor_row = 0;
+            self.cursor_col = 0;
+            self.wrap_pending = false;
+            self.reset_attributes();
+            self.charsets = Charsets::default();
+            self.modes.origin = false;
+            return;
+        };
+        // The grid may have shrunk since the cursor was saved
+        self.cursor_row = saved.row.min(rows - 1);
+        self.cursor_col = saved.col.min(cols - 1);
+        self.wrap_pending = saved.wrap_pending;
+        self.fg = saved.fg;
+        self.bg = saved.bg;
+        self.bold = saved.bold;
+        self.dim = saved.dim;
+        self.italic = saved.italic;
+        self.underline = saved.underline;
+        self.charsets = saved.charsets;
+        self.modes.origin = saved.origin;
+    }
+
+    /// Queue a response to be written back to the PTY
+    pub fn reply(&mut self, bytes: &[u8]) {
+        self.replies.extend_from_slice(bytes);
+    }
+
+    /// Take all queued responses
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.replies)
+    }
+
+    /// Queue an event for the widget
+    pub fn emit(&mut self, event: TermEvent) {
+        self.events.push(event);
+    }
+
+    /// Take all queued events
+    pub fn take_events(&mut self) -> Vec<TermEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The child exited after `run_time`: finish the command that was still running
+    ///
+    /// Without shell integration marks the child itself is the finished command;
+    /// a shell that reported prompts and exits at one finishes nothing.
+    pub fn child_exited(&mut self, command: &str, exit_code: Option<i32>, run_time: Duration) {
+        let block = if self.commands.blocks().is_empty() {
+            Some(CommandBlock::exited(command, exit_code, run_time))
+        } else {
+            let line = self.scrolled_lines + self.cursor_row;
+            self.commands.command_exited(line, self.cursor_col)
+        };
+        if let Some(block) = block {
+            self.emit(TermEvent::CommandFinished(block));
+        }
+    }
+
+    /// Longest OSC payload kept, leaving room for base64 clipboard contents and images
+    fn osc_capacity(&self) -> usize {
+        (self.clipboard_limit.div_ceil(3) * 4 + 16).max(self.apc_capacity())
+    }
+
+    /// Longest APC payload kept, leaving room for a base64 image within the memory limit
+    fn apc_capacity(&self) -> usize {
+        MAX_SEQUENCE_LEN.max(self.images.limit.div_ceil(3) * 4 + 256)
+    }
+
+    /// Set the theme's ANSI colors (`None` for the built-in ones), keeping colors set by OSC 4
+    pub fn set_theme_palette(&mut self, ansi: Option<[Color; 16]>) {
+        let mut base = default_palette();
+        if let Some(ansi) = ansi {
+            base[..16].copy_from_slice(&ansi);
+        }
+        for ((color, old), new) in self.palette.iter_mut().zip(&self.base_palette).zip(&base) {
+            if color == old {
+                *color = *new;
+            }
+        }
+        self.base_palette = base;
+    }
+
+    /// Foreground (0), background (1) or cursor (2) color, with overrides applied
+    fn dynamic_color(&self, index: usize) -> Color {
+        self.color_overrides[index].unwrap_or(self.theme_colors[index])
+    }
+
+    /// Set the working directory, emitting an event if it changed
+    pub fn set_cwd(&mut self, cwd: PathBuf) {
+        if self.cwd.as_ref() != Some(&cwd) {
+            self.cwd = Some(cwd.clone());
+            self.emit(TermEvent::CwdChanged(cwd));
+        }
+    }
+
+    /// Ring the bell, unless it already rang within `BELL_INTERVAL`
+    fn bell(&mut self) {
+        let now = Instant::now();
+        if self
+            .last_bell
+            .is_none_or(|last| now.duration_since(last) >= BELL_INTERVAL)
+        {
+            self.last_bell = Some(now);
+            self.emit(TermEvent::Bell);
+        }
+    }
+
+    /// Set the window title, emitting an event if it changed
+    fn set_title(&mut self, title: String) {
+        if self.title != title {
+            self.title.clone_from(&title);
+            self.emit(TermEvent::TitleChanged(title));
+        }
+    }
+}
+
+/// Parsed parameters of a CSI sequence
+struct CsiParams {
+    /// Private marker (`<`, `=`, `>` or `?`), if any
+    prefix: Option<u8>,
+    /// Numeric parameters (sub-parameters after `:` are dropped)
+    params: Vec<u16>,
+    /// Intermediate byte (e.g. the space in `CSI 2 SP q`), if any
+    intermediate: Option<u8>,
+}
+
+impl CsiParams {
+    fn parse(buf: &[u8]) -> Self {
+        let (prefix, rest) = match buf.first() {
+            Some(&b @ (b'<' | b'=' | b'>' | b'?')) => (Some(b), &buf[1..]),
+            _ => (None, buf),
+        };
+        let (rest, intermediate) = match rest.last() {
+            Some(&b @ 0x20..=0x2f) => (&rest[..rest.len() - 1], Some(b)),
+            _ => (rest, None),
+        };
+        let params = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(|&b| b == b';')
+                .map(|p| {
+                    let main = p.split(|&b| b == b':').next().unwrap_or_default();
+                    std::str::from_utf8(main)
+                        .ok()
+                        .and_then(|s| s.parse().ok())
+                        .unwrap_or(0)
+                })
+                .collect()
+        };
+        Self {
+            prefix,
+            params,
+            intermediate,
+        }
+    }
+
+    /// Get parameter `i`, or `default` if missing
+    fn get(&self, i: usize, default: u16) -> u16 {
+        self.params.get(i).copied().unwrap_or(default)
+    }
+}
+
+/// Process a single byte of terminal output
+pub(crate) fn process_byte(
+    state: &mut TermState,
+    grid: &mut Grid,
+    byte: u8,
+    rows: usize,
+    cols: usize,
+) {
+    match state.escape_state {
+        EscapeState::Normal => process_normal(state, grid, byte, rows, cols),
+        EscapeState::Escape => process_escape(state, grid, byte),
+        EscapeState::LineAttribute => line_attribute(state, grid, byte),
+        EscapeState::Designate(slot) => {
+            // Intermediates (e.g. `%` in `ESC ( % 5`) come before the final byte
+            if !(0x20..=0x2f).contains(&byte) {
+                state.charsets.designate(slot, byte);
+                state.escape_state = EscapeState::Normal;
+            }
+        }
+        EscapeState::Osc => {
+            // OSC sequences end with BEL (0x07) or ST (ESC \)
+            match byte {
+                0x07 => {
+                    dispatch_osc(state, grid, b"\x07");
+                    state.escape_state = EscapeState::Normal;
+                    state.escape_buf.clear();
+                }
+                0x1b => state.escape_state = EscapeState::OscEscape,
+                _ if state.escape_buf.len() < state.osc_capacity() => state.escape_buf.push(byte),
+                _ => {}
+            }
+        }
+        EscapeState::OscEscape => {
+            if byte == b'\\' {
+                dispatch_osc(state, grid, b"\x1b\\");
+                state.escape_state = EscapeState::Normal;
+                state.escape_buf.clear();
+            } else {
+                state.escape_state = EscapeState::Osc;
+            }
+        }
+        EscapeState::Dcs => {
+            // DCS sequences end with ST (ESC \)
+            if byte == 0x1b {
+                state.escape_state = EscapeState::DcsEscape;
+            } else if byte == b'q'
+                && state
+                    .escape_buf
+                    .iter()
+                    .all(|&b| b.is_ascii_digit() || b == b';')
+            {
+                // Sixel data is streamed into the decoder instead of buffered
+                let decoder = SixelDecoder::new(&state.escape_buf, state.images.limit);
+                state.sixel = Some(Box::new(decoder));
+                state.escape_state = EscapeState::Sixel;
+                state.escape_buf.clear();
+            } else if state.escape_buf.len() < MAX_SEQUENCE_LEN {
+                state.escape_buf.push(byte);
+            }
+        }
+        EscapeState::DcsEscape => {
+            if byte == b'\\' {
+                dispatch_dcs(state);
+                state.escape_state = EscapeState::Normal;
+                state.escape_buf.clear();
+            } else {
+                state.escape_state = EscapeState::Dcs;
+            }
+        }
+        EscapeState::Sixel => {
+            if byte == 0x1b {
+                state.escape_state = EscapeState::SixelEscape;
+            } else if let Some(decoder) = &mut state.sixel {
+                decoder.feed(byte);
+            }
+        }
+        EscapeState::SixelEscape => {
+            if byte == b'\\' {
+                finish_sixel(state, grid, rows, cols);
+                state.escape_state = EscapeState::Normal;
+            } else {
+                state.escape_state = EscapeState::Sixel;
+            }
+        }
+        EscapeState::Apc => {
+            // APC sequences end with ST (ESC \)
+            if byte == 0x1b {
+                state.escape_state = EscapeState::ApcEscape;
+            } else if state.escape_buf.len() < state.apc_capacity() {
+                state.escape_buf.push(byte);
+            }
+        }
+        EscapeState::ApcEscape => {
+            if byte == b'\\' {
+                dispatch_apc(state, grid);
+                state.escape_state = EscapeState::Normal;
+                state.escape_buf.clear();
+            } else {
+                state.escape_state = EscapeState::Apc;
+            }
+        }
+        EscapeState::Csi => {
+            if (0x40..=0x7e).contains(&byte) {
+                // End of CSI sequence
+                dispatch_csi(state, grid, byte);
+                state.escape_state = EscapeState::Normal;
+                state.escape_buf.clear();
+            } else {
+                // Buffer the parameter bytes
+                state.escape_buf.push(byte);
+            }
+        }
+    }
+}
+
+/// Process the byte after ESC
+fn process_escape(state: &mut TermState, grid: &mut Grid, byte: u8) {
+    state.escape_state = EscapeState::Normal;
+    match byte {
+        b'[' => state.escape_state = EscapeState::Csi,
+        b']' => state.escape_state = EscapeState::Osc,
+        b'P' => state.escape_state = EscapeState::Dcs,
+        b'_' => state.escape_state = EscapeState::Apc,
+        // Character set designation for G0-G3 (94- and 96-character sets)
+        b'(' => state.escape_state = EscapeState::Designate(0),
+        b')' | b'-' => state.escape_state = EscapeState::Designate(1),
+        b'*' | b'.' => state.escape_state = EscapeState::Designate(2),
+        b'+' | b'/' => state.escape_state = EscapeState::Designate(3),
+        // Line size (DECDHL, DECSWL, DECDWL) and DECALN
+        b'#' => state.escape_state = EscapeState::LineAttribute,
+        // SS2 / SS3 - single shift
+        b'N' => state.charsets.single_shift(2),
+        b'O' => state.charsets.single_shift(3),
+        // LS2 / LS3 - lock shift
+        b'n' => state.charsets.lock_shift(2),
+        b'o' => state.charsets.lock_shift(3),
+        // HTS - set a tab stop at the cursor column
+        b'H' => grid.set_tab_stop(state.cursor_col, true),
+        // DECSC / DECRC - save and restore the cursor
+        b'7' => state.save_cursor(),
+        b'8' => state.restore_cursor(grid.rows(), grid.cols()),
+        // RIS - full reset
+        b'c' => full_reset(state, grid),
+        // Other single-character sequences are ignored
+        _ => {}
+    }
+}
+
+/// Process a byte outside of any escape sequence
+fn process_normal(state: &mut TermState, grid: &mut Grid, byte: u8, rows: usize, cols: usize) {
+    match byte {
+        // Escape - start escape sequence
+        0x1b => {
+            state.escape_state = EscapeState::Escape;
+            state.escape_buf.clear();
+        }
+        // Line feed, vertical tab and form feed (also a carriage return in LNM)
+        b'\n' | 0x0b | 0x0c => {
+            grid.set_wrapped(state.cursor_row, false);
+            line_feed(state, grid, rows, cols);
+            if state.modes.newline {
+                state.cursor_col = 0;
+            }
+        }
+        // Carriage return
+        b'\r' => {
+            state.cursor_col = 0;
+            state.wrap_pending = false;
+        }
+        // Bell
+        0x07 => state.bell(),
+        // SI / SO - invoke G0 / G1
+        0x0f => state.charsets.lock_shift(0),
+        0x0e => state.charsets.lock_shift(1),
+        // Backspace
+        0x08 if state.cursor_col > 0 => {
+            state.cursor_col -= 1;
+            state.wrap_pending = false;
+        }
+        // Tab
+        b'\t' => {
+            let last_col = line_cols(grid, state.cursor_row) - 1;
+            state.cursor_col = grid.next_tab_stop(state.cursor_col).min(last_col);
+            state.wrap_pending = false;
+        }
+        // Printable characters
+        0x20..=0x7e | 0x80..=0xff => print(state, grid, byte, rows, cols),
+        // Other control characters - ignore
+        _ => {}
+    }
+}
+
+/// Print a character at the cursor and advance it
+fn print(state: &mut TermState, grid: &mut Grid, byte: u8, rows: usize, cols: usize) {
+    let c = state.charsets.map(byte as char);
+    let cell = Cell {
+        c,
+        fg: state.fg,
+        bg: state.bg,
+        style: Style {
+            bold: state.bold,
+            dim: state.dim,
+            italic: state.italic,
+            underline: state.underline,
+            strikethrough: false,
+            inverse: false,
+        },
+        link: state.link.clone(),
+    };
+    // Like xterm, the cursor stays in the last column until the next character
+    if state.wrap_pending && state.modes.autowrap {
+        grid.set_wrapped(state.cursor_row, true);
+        state.cursor_col = 0;
+        line_feed(state, grid, rows, cols);
+    }
+    state.wrap_pending = false;
+    if state.modes.insert {
+        grid.insert_cells(state.cursor_row, state.cursor_col, 1);
+    }
+    grid.set(state.cursor_row, state.cursor_col, cell);
+    if state.cursor_col + 1 < line_cols(grid, state.cursor_row) {
+        state.cursor_col += 1;
+    } else {
+        state.wrap_pending = state.modes.autowrap;
+    }
+}
+
+/// Number of columns usable on a row (half of them on double-width rows)
+fn line_cols(grid: &Grid, row: usize) -> usize {
+    if grid.line_size(row).is_double_width() {
+        (grid.cols() / 2).max(1)
+    } else {
+        grid.cols()
+    }
+}
+
+/// Handle `ESC # n`: the cursor row's size (DECDHL, DECSWL, DECDWL) or DECALN
+fn line_attribute(state: &mut TermState, grid: &mut Grid, byte: u8) {
+    state.escape_state = EscapeState::Normal;
+    let size = match byte {
+        b'3' => LineSize::DoubleHeightTop,
+        b'4' => LineSize::DoubleHeightBottom,
+        b'5' => LineSize::Single,
+        b'6' => LineSize::DoubleWidth,
+        // DECALN - fill the screen with `E` to check alignment
+        b'8' => {
+            grid.clear();
+            for row in 0..grid.rows() {
+                for col in 0..grid.cols() {
+                    grid.set(row, col, Cell::new('E'));
+                }
+            }
+            state.cursor_row = 0;
+            state.cursor_col = 0;
+            state.wrap_pending = false;
+            return;
+        }
+        _ => return,
+    };
+    grid.set_line_size(state.cursor_row, size);
+    // Only the left half of a double-width row can be written to
+    state.cursor_col = state.cursor_col.min(line_cols(grid, state.cursor_row) - 1);
+}
+
+/// Move the cursor down a row, scrolling at the bottom of the scrolling region
+fn line_feed(state: &mut TermState, grid: &mut Grid, rows: usize, cols: usize) {
+    state.wrap_pending = false;
+    let (top, bottom) = state.margins(rows);
+    if state.cursor_row == bottom {
+        scroll_up(grid, top, bottom, cols);
+        // Only lines leaving the whole screen count as scrolled off
+        if state.scroll_region.is_none() {
+            state.scrolled_lines += 1;
+            state.images.prune(state.scrolled_lines);
+        }
+    } else {
+        state.cursor_row = (state.cursor_row + 1).min(rows - 1);
+    }
+}
+
+/// CUP / HVP - move the cursor to a 1-based row and column (within the margins in DECOM)
+fn cursor_position(state: &mut TermState, grid: &Grid, row: u16, col: u16) {
+    let (top, bottom) = if state.modes.origin {
+        state.margins(grid.rows())
+    } else {
+        (0, grid.rows() - 1)
+    };
+    state.cursor_row = (top + usize::from(row.max(1)) - 1).min(bottom);
+    let last_col = line_cols(grid, state.cursor_row) - 1;
+    state.cursor_col = (usize::from(col.max(1)) - 1).min(last_col);
+    state.wrap_pending = false;
+}
+
+/// DECSTBM - scroll only 1-based rows `top..=bottom` (0 is the last row), homing the cursor
+fn set_scroll_region(state: &mut TermState, grid: &Grid, top: u16, bottom: u16) {
+    let rows = grid.rows();
+    let top = usize::from(top.max(1)) - 1;
+    let bottom = if bottom == 0 {
+        rows
+    } else {
+        usize::from(bottom).min(rows)
+    } - 1;
+    if top >= bottom {
+        return;
+    }
+    state.scroll_region = (top > 0 || bottom < rows - 1).then_some((top, bottom));
+    cursor_position(state, grid, 1, 1);
+}
+
+/// DSR - report the status (5) or the cursor position (6, also as DECXCPR with `?`)
+fn device_status(state: &mut TermState, grid: &Grid, private: bool, n: u16) {
+    match n {
+        5 if !private => state.reply(b"\x1b[0n"),
+        6 => {
+            let row = report_row(state, grid.rows());
+            let prefix = if private { "?" } else { "" };
+            let reply = format!("\x1b[{prefix}{row};{}R", state.cursor_col + 1);
+            state.reply(reply.as_bytes());
+        }
+        _ => {}
+    }
+}
+
+/// Cursor row for position reports, counted from the top margin in DECOM
+fn report_row(state: &TermState, rows: usize) -> usize {
+    let top = if state.modes.origin {
+        state.margins(rows).0
+    } else {
+        0
+    };
+    state.cursor_row.saturating_sub(top) + 1
+}
+
+/// Place a finished Sixel image at the cursor and move below it
+fn finish_sixel(state: &mut TermState, grid: &mut Grid, rows: usize, cols: usize) {
+    let Some(image) = state.sixel.take().and_then(|decoder| decoder.finish()) else {
+        return;
+    };
+    let size = (image.width, image.height);
+    let Some(key) = state.images.add(image, None, None) else {
+        return;
+    };
+    let Some(url) = state.images.url(key) else {
+        return;
+    };
+    let line = state.scrolled_lines + state.cursor_row;
+    let placement = Placement::new(line, state.cursor_col, size, state.cell_size, url);
+    let image_rows = placement.rows;
+    state.images.place(key, placement);
+    // Text continues on the row below the image, scrolling it up if needed
+    for _ in 0..image_rows {
+        line_feed(state, grid, rows, cols);
+    }
+}
+
+/// Handle a complete APC sequence (kitty graphics commands start with `G`)
+fn dispatch_apc(state: &mut TermState, grid: &mut Grid) {
+    let buf = std::mem::take(&mut state.escape_buf);
+    let Some(command) = buf.strip_prefix(b"G").and_then(GraphicsCommand::parse) else {
+        return;
+    };
+    // Chunks after the first only carry `m` and more payload
+    let command = if let Some(mut transfer) = state.kitty_transfer.take() {
+        transfer.push(&command.payload, state.apc_capacity());
+        if command.more {
+            state.kitty_transfer = Some(transfer);
+            return;
+        }
+        match transfer.finish() {
+            Ok(command) => command,
+            Err(command) => {
+                graphics_reply(
+                    state,
+                    &command,
+                    None,
+                    &Err("EFBIG:image too large".to_string()),
+                );
+                return;
+            }
+        }
+    } else if command.more {
+        state.kitty_transfer = Some(Transfer::new(command));
+        return;
+    } else {
+        command
+    };
+
+    let (id, result) = match command.action {
+        Action::Query => (None, decode_image(&command, state.images.limit).map(|_| ())),
+        Action::Transmit | Action::TransmitDisplay => transmit_image(state, grid, &command),
+        Action::Display => {
+            let key = command
+                .id
+                .and_then(|id| state.images.find(id))
+                .or_else(|| command.number.and_then(|n| state.images.find_number(n)));
+            match key {
+                Some(key) => (
+                    state.images.id(key),
+                    place_image(state, grid, key, &command),
+                ),
+                None => (None, Err("ENOENT:no such image".to_string())),
+            }
+        }
+        Action::Delete => {
+            delete_images(state, &command);
+            return;
+        }
+    };
+    graphics_reply(state, &command, id, &result);
+}
+
+/// Queue the response to a graphics command, unless it is suppressed
+fn graphics_reply(
+    state: &mut TermState,
+    command: &GraphicsCommand,
+    id: Option<u32>,
+    result: &Result<(), String>,
+) {
+    let result = result.as_ref().map_err(String::as_str).copied();
+    if let Some(reply) = command.response(id, result) {
+        state.reply(reply.as_bytes());
+    }
+}
+
+/// Store a transmitted image (`a=t` / `a=T`), returning its id and the result
+fn transmit_image(
+    state: &mut TermState,
+    grid: &mut Grid,
+    command: &GraphicsCommand,
+) -> (Option<u32>, Result<(), String>) {
+    let image = match decode_image(command, state.images.limit) {
+        Ok(image) => image,
+        Err(error) => return (command.id, Err(error)),
+    };
+    // Images sent with only a number get a free id, reported back
+    let id = command
+        .id
+        .or_else(|| command.number.map(|_| state.images.free_id()));
+    let Some(key) = state.images.add(image, id, command.number) else {
+        return (id, Err("EFBIG:image too large".to_string()));
+    };
+    let result = if command.action == Action::TransmitDisplay {
+        place_image(state, grid, key, command)
+    } else {
+        Ok(())
+    };
+    (id, result)
+}
+
+/// Place a stored image at the cursor as described by `command`
+fn place_image(
+    state: &mut TermState,
+    grid: &mut Grid,
+    key: u64,
+    command: &GraphicsCommand,
+) -> Result<(), String> {
+    let image = state
+        .images
+        .image(key)
+        .ok_or_else(|| "ENOENT:no such image".to_string())?;
+    // Source rectangle, clipped to the image (0 means up to the edge)
+    let (src_x, src_y, src_width, src_height) = command.source;
+    let src_x = src_x.min(image.width);
+    let src_y = src_y.min(image.height);
+    let src_width = match src_width {
+        0 => image.width - src_x,
+        width => width.min(image.width - src_x),
+    };
+    let src_height = match src_height {
+        0 => image.height - src_y,
+        height => height.min(image.height - src_y),
+    };
+    if src_width == 0 || src_height == 0 {
+        return Err("EINVAL:empty source rectangle".to_string());
+    }
+    let url = if (src_x, src_y, src_width, src_height) == (0, 0, image.width, image.height) {
+        state.images.url(key)
+    } else {
+        image
+            .crop(src_x, src_y, src_width, src_height)
+            .to_data_url()
+            .map(Into::into)
+    }
+    .ok_or_else(|| "EINVAL:cannot encode image".to_string())?;
+
+    // Display size: cells if given (keeping the aspect ratio if only one is), else pixels
+    let (cell_width, cell_height) = (state.cell_size.0.max(1), state.cell_size.1.max(1));
+    let scale = |value: u32, to: u32, from: u32| {
+        u32::try_from(u64::from(value) * u64::from(to) / u64::from(from)).unwrap_or(u32::MAX)
+    };
+    let (width, height) = match (command.cols, command.rows) {
+        (0, 0) => (src_width, src_height),
+        (c, 0) => {
+            let width = c.saturating_mul(cell_width);
+            (width, scale(src_height, width, src_width))
+        }
+        (0, r) => {
+            let height = r.saturating_mul(cell_height);
+            (scale(src_width, height, src_height), height)
+        }
+        (c, r) => (c.saturating_mul(cell_width), r.saturating_mul(cell_height)),
+    };
+    // The offset counts towards the cells covered
+    let (offset_x, offset_y) = command.offset;
+    let covered = (
+        offset_x.saturating_add(width),
+        offset_y.saturating_add(height),
+    );
+    let line = state.scrolled_lines + state.cursor_row;
+    let placement = Placement {
+        id: command.placement,
+        width,
+        height,
+        offset: command.offset,
+        z: command.z,
+        ..Placement::new(line, state.cursor_col, covered, state.cell_size, url)
+    };
+    if show_image(state, grid, key, placement, !command.no_move) {
+        Ok(())
+    } else {
+        Err("ENOSPC:out of image memory".to_string())
+    }
+}
+
+/// Show image `key` and, if `move_cursor`, move the cursor to the cell after
+/// the image's last column, on its last row
+///
+/// Returns `false` if the placement didn't fit in the image memory limit.
+fn show_image(
+    state: &mut TermState,
+    grid: &mut Grid,
+    key: u64,
+    placement: Placement,
+    move_cursor: bool,
+) -> bool {
+    let (rows, cols) = (grid.rows(), grid.cols());
+    let (image_rows, image_cols) = (placement.rows, placement.cols);
+    if !state.images.place(key, placement) {
+        return false;
+    }
+    if move_cursor {
+        for _ in 1..image_rows {
+            line_feed(state, grid, rows, cols);
+        }
+        state.cursor_col = (state.cursor_col + image_cols).min(cols - 1);
+        state.wrap_pending = false;
+    }
+    true
+}
+
+/// Handle an iTerm2 file transfer (OSC 1337), showing inline images
+fn inline_file(state: &mut TermState, grid: &mut Grid, data: &str) {
+    let capacity = state.osc_capacity();
+    let (args, contents) = match parse_osc1337(data) {
+        Some(FileCommand::File(args, contents)) => (args, contents),
+        Some(FileCommand::MultipartFile(args)) => {
+            state.iterm_upload = Some((args, String::new()));
+            return;
+        }
+        Some(FileCommand::FilePart(part)) => {
+            if let Some((_, contents)) = &mut state.iterm_upload {
+                if contents.len() + part.len() > capacity {
+                    state.iterm_upload = None;
+                } else {
+                    contents.push_str(&part);
+                }
+            }
+            return;
+        }
+        Some(FileCommand::FileEnd) => match state.iterm_upload.take() {
+            Some(upload) => upload,
+            None => return,
+        },
+        None => return,
+    };
+    if !args.inline {
+        return;
+    }
+    let Some(image) =
+        decode_contents(&contents).and_then(|file| Image::decode(&file, state.images.limit).ok())
+    else {
+        return;
+    };
+    let cell = (state.cell_size.0.max(1), state.cell_size.1.max(1));
+    let screen = (
+        cell.0
+            .saturating_mul(u32::try_from(grid.cols()).unwrap_or(u32::MAX)),
+        cell.1
+            .saturating_mul(u32::try_from(grid.rows()).unwrap_or(u32::MAX)),
+    );
+    let size = args.display_size((image.width, image.height), cell, screen);
+    let Some(key) = state.images.add(image, None, None) else {
+        return;
+    };
+    let Some(url) = state.images.url(key) else {
+        return;
+    };
+    let line = state.scrolled_lines + state.cursor_row;
+    let placement = Placement::new(line, state.cursor_col, size, cell, url);
+    show_image(state, grid, key, placement, !args.no_move);
+}
+
+/// Delete placements (lowercase `d=`) or placements and image data (uppercase)
+fn delete_images(state: &mut TermState, command: &GraphicsCommand) {
+    let free = command.delete.is_ascii_uppercase();
+    let top = state.scrolled_lines;
+    let cursor = (top + state.cursor_row, state.cursor_col);
+    // `x` and `y` name 1-based cells for these deletions
+    let cell = (
+        top + command.source.1.saturating_sub(1) as usize,
+        command.source.0.saturating_sub(1) as usize,
+    );
+    let id = match command.delete.to_ascii_lowercase() {
+        b'i' => command.id,
+        b'n' => command
+            .number
+            .and_then(|n| state.images.find_number(n))
+            .and_then(|key| state.images.id(key)),
+        _ => None,
+    };
+    let images = &mut state.images;
+    match command.delete.to_ascii_lowercase() {
+        b'a' => images.delete(free, |_, _| true),
+        b'i' | b'n' => {
+            let Some(id) = id else {
+                return;
+            };
+            match (command.placement, images.find(id)) {
+                // Deleting an image's data removes it even if it isn't shown
+                (0, Some(key)) if free => images.remove(key),
+                (placement, _) => images.delete(free, |image, p| {
+                    image == Some(id) && (placement == 0 || p.id == placement)
+                }),
+            }
+        }
+        b'c' => images.delete(free, |_, p| p.covers(cursor.0, cursor.1)),
+        b'p' => images.delete(free, |_, p| p.covers(cell.0, cell.1)),
+        b'x' => images.delete(free, |_, p| (p.col..p.col + p.cols).contains(&cell.1)),
+        b'y' => images.delete(free, |_, p| (p.line..p.line + p.rows).contains(&cell.0)),
+        b'z' => images.delete(free, |_, p| p.z == command.z),
+        _ => {}
+    }
+}
+
+/// Handle a complete CSI sequence ending in `action`
+fn dispatch_csi(state: &mut TermState, grid: &mut Grid, action: u8) {
+    let params = CsiParams::parse(&state.escape_buf);
+    match (params.prefix, action) {
+        // SGR - Select Graphic Rendition
+        (None, b'm') => process_sgr(state),
+        // DECSET / DECRST - DEC private modes
+        (Some(b'?'), b'h' | b'l') => {
+            for &mode in &params.params {
+                set_private_mode(state, grid, mode, action == b'h');
+            }
+        }
+        // TBC - clear the tab stop at the cursor (0) or all tab stops (3)
+        (None, b'g') => match params.get(0, 0) {
+            0 => grid.set_tab_stop(state.cursor_col, false),
+            3 => grid.clear_tab_stops(),
+            _ => {}
+        },
+        // CHT / CBT - move forward / backward by a number of tab stops
+        (None, b'I') => {
+            for _ in 0..params.get(0, 1).max(1) {
+                state.cursor_col = grid.next_tab_stop(state.cursor_col);
+            }
+            state.wrap_pending = false;
+        }
+        (None, b'Z') => {
+            for _ in 0..params.get(0, 1).max(1) {
+                state.cursor_col = grid.prev_tab_stop(state.cursor_col);
+            }
+            state.wrap_pending = false;
+        }
+        // CUP / HVP - cursor position
+        (None, b'H' | b'f') => cursor_position(state, grid, params.get(0, 1), params.get(1, 1)),
+        // DECSTBM - set the top and bottom margins
+        (None, b'r') if params.intermediate.is_none() => {
+            set_scroll_region(state, grid, params.get(0, 1), params.get(1, 0));
+        }
+        // SCOSC / SCORC - save and restore the cursor
+        (None, b's') => state.save_cursor(),
+        (None, b'u') => state.restore_cursor(grid.rows(), grid.cols()),
+        // DECSTR - soft reset
+        (None, b'p') if params.intermediate == Some(b'!') => soft_reset(state),
+        // SM / RM - ANSI modes
+        (None, b'h' | b'l') => {
+            for &mode in &params.params {
+                state.modes.set_ansi(mode, action == b'h');
+            }
+        }
+        // DSR - device status and cursor position reports
+        (None | Some(b'?'), b'n') => {
+            device_status(state, grid, params.prefix.is_some(), params.get(0, 0));
+        }
+        // DA - primary, secondary and tertiary device attributes
+        (None, b'c') if params.get(0, 0) == 0 => state.reply(PRIMARY_DA.as_bytes()),
+        (Some(b'>'), b'c') if params.get(0, 0) == 0 => {
+            let reply = format!("\x1b[>1;{};0c", version_number());
+            state.reply(reply.as_bytes());
+        }
+        (Some(b'='), b'c') if params.get(0, 0) == 0 => state.reply(b"\x1bP!|00000000\x1b\\"),
+        // XTVERSION - terminal name and version
+        (Some(b'>'), b'q') if params.get(0, 0) == 0 => {
+            let reply = format!(
+                "\x1bP>|{}({})\x1b\\",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            );
+            state.reply(reply.as_bytes());
+        }
+        // DECRQM - request DEC private / ANSI mode
+        (Some(b'?'), b'p') if params.intermediate == Some(b'$') => {
+            let mode = params.get(0, 0);
+            let value = mode_report(private_mode(state, mode));
+            let reply = format!("\x1b[?{mode};{value}$y");
+            state.reply(reply.as_bytes());
+        }
+        (None, b'p') if params.intermediate == Some(b'$') => {
+            let mode = params.get(0, 0);
+            let reply = format!("\x1b[{mode};{}$y", mode_report(state.modes.ansi(mode)));
+            state.reply(reply.as_bytes());
+        }
+        // XTWINOPS - push/pop title and icon name (0 = both, 1 = icon, 2 = title)
+        (None, b't') if matches!(params.get(0, 0), 22 | 23) => {
+            title_stack(state, params.get(0, 0) == 22, params.get(1, 0));
+        }
+        // DECSCUSR - cursor shape and blink
+        (None, b'q') if params.intermediate == Some(b' ') => {
+            let style = CursorStyle::from_decscusr(params.get(0, 0));
+            state.cursor_shape = style.map(|s| s.shape);
+            state.cursor_blink = style.map(|s| s.blinking);
+        }
+        // Kitty keyboard protocol: set, push, pop and query flags
+        (Some(b'='), b'u') => {
+            state.keyboard.set_kitty(params.get(0, 0), params.get(1, 1));
+        }
+        (Some(b'>'), b'u') => state.keyboard.push_kitty(params.get(0, 0)),
+        (Some(b'<'), b'u') => state.keyboard.pop_kitty(params.get(0, 1).into()),
+        (Some(b'?'), b'u') => {
+            let reply = format!("\x1b[?{}u", state.keyboard.kitty_flags());
+            state.reply(reply.as_bytes());
+        }
+        // xterm modifyOtherKeys: set/reset (XTMODKEYS), disable and query (XTQMODKEYS)
+        (Some(b'>'), b'm') if params.get(0, 0) == 4 => {
+            state.keyboard.set_modify_other_keys(params.get(1, 0));
+        }
+        (Some(b'>'), b'n') if params.get(0, 0) == 4 => state.keyboard.set_modify_other_keys(0),
+        (Some(b'?'), b'm') if params.get(0, 0) == 4 => {
+            let reply = format!("\x1b[>4;{}m", state.keyboard.modify_other_keys());
+            state.reply(reply.as_bytes());
+        }
+        // Other CSI sequences (cursor movement, etc.) - ignore for now
+        _ => {}
+    }
+}
+
+/// Push (XTWINOPS 22) or pop (23) the icon name and/or title
+fn title_stack(state: &mut TermState, push: bool, which: u16) {
+    if push {
+        if state.title_stack.len() >= TITLE_STACK_LIMIT {
+            state.title_stack.remove(0);
+        }
+        let entry = (state.icon_name.clone(), state.title.clone());
+        state.title_stack.push(entry);
+    } else if let Some((icon_name, title)) = state.title_stack.pop() {
+        if which != 2 {
+            state.icon_name = icon_name;
+        }
+        if which != 1 {
+            state.set_title(title);
+        }
+    }
+}
+
+/// Handle a complete OSC sequence (payload in `escape_buf`)
+///
+/// Replies end with the same `terminator` (BEL or ST) as the request.
+fn dispatch_osc(state: &mut TermState, grid: &mut Grid, terminator: &[u8]) {
+    let payload = String::from_utf8_lossy(&state.escape_buf).into_owned();
+    let (command, data) = payload.split_once(';').unwrap_or((payload.as_str(), ""));
+    match command {
+        // Icon name and window title
+        "0" | "1" | "2" => {
+            let text: String = data.chars().filter(|c| !c.is_control()).collect();
+            if command != "2" {
+                state.icon_name.clone_from(&text);
+            }
+            if command != "1" {
+                state.set_title(text);
+            }
+        }
+        // Working directory as a `file://host/path` URL
+        "7" => {
+            if let Some(cwd) = parse_osc7(data) {
+                state.cwd_reported = true;
+                state.set_cwd(cwd);
+            }
+        }
+        // Notifications (iTerm2 and rxvt styles)
+        "9" => {
+            if let Some(notification) = parse_osc9(data) {
+                state.emit(TermEvent::Notification(notification));
+            }
+        }
+        "777" => {
+            if let Some(notification) = parse_osc777(data) {
+                state.emit(TermEvent::Notification(notification));
+            }
+        }
+        // Semantic prompt marks from shell integration
+        "133" => semantic_prompt(state, grid, data),
+        // iTerm2 inline images
+        "1337" => inline_file(state, grid, data),
+        // Hyperlink start (or end, with an empty URI)
+        "8" => state.link = Hyperlink::from_osc8(data).map(Arc::new),
+        // Clipboard access; the widget applies the clipboard policy
+        "52" => match parse_osc52(data, state.clipboard_limit) {
+            Some(ClipboardOp::Set(text)) => state.emit(TermEvent::ClipboardWrite(text)),
+            Some(ClipboardOp::Query { selection }) => {
+                state.emit(TermEvent::ClipboardRead { selection });
+            }
+            None => {}
+        },
+        // Palette colors: pairs of index and spec (or `?` to query)
+        "4" => {
+            let mut parts = data.split(';');
+            while let (Some(index), Some(spec)) = (parts.next(), parts.next()) {
+                let Ok(index) = index.parse::<u8>() else {
+                    continue;
+                };
+                if spec == "?" {
+                    let color = state.palette[usize::from(index)].to_xparse();
+                    state.reply(format!("\x1b]4;{index};{color}").as_bytes());
+                    state.reply(terminator);
+                } else if let Some(color) = Color::from_xparse(spec) {
+                    state.palette[usize::from(index)] = color;
+                }
+            }
+        }
+        // Foreground, background and cursor colors; extra specs apply to the next ones
+        "10" | "11" | "12" => {
+            let first = match command {
+                "10" => 0,
+                "11" => 1,
+                _ => 2,
+            };
+            for (index, spec) in (first..3).zip(data.split(';')) {
+                if spec == "?" {
+                    let color = state.dynamic_color(index).to_xparse();
+                    state.reply(format!("\x1b]{};{color}", 10 + index).as_bytes());
+                    state.reply(terminator);
+                } else if let Some(color) = Color::from_xparse(spec) {
+                    state.color_overrides[index] = Some(color);
+                }
+            }
+        }
+        // Reset palette colors (all of them without parameters)
+        "104" => {
+            if data.is_empty() {
+                state.palette = state.base_palette;
+            }
+            for index in data.split(';').filter_map(|i| i.parse::<usize>().ok()) {
+                if let Some(&color) = state.base_palette.get(index) {
+                    state.palette[index] = color;
+                }
+            }
+        }
+        // Reset foreground, background and cursor colors
+        "110" => state.color_overrides[0] = None,
+        "111" => state.color_overrides[1] = None,
+        "112" => state.color_overrides[2] = None,
+        // Other OSC commands - ignore for now
+        _ => {}
+    }
+}
+
+/// Handle an OSC 133 mark (`A`, `B`, `C` or `D[;exit]`, options after `;`)
+fn semantic_prompt(state: &mut TermState, grid: &mut Grid, data: &str) {
+    let mut parts = data.split(';');
+    let line = state.scrolled_lines + state.cursor_row;
+    let col = state.cursor_col;
+    let mark = match parts.next() {
+        Some("A") => {
+            state.commands.prompt_start(line);
+            PromptMark::PromptStart
+        }
+        Some("B") => {
+            state.commands.command_start(line, col);
+            PromptMark::CommandStart
+        }
+        Some("C") => {
+            let scrolled = state.scrolled_lines;
+            state.commands.output_start(grid, scrolled, (line, col));
+            PromptMark::OutputStart
+        }
+        Some("D") => {
+            let exit_code = parts.next().and_then(|code| code.parse().ok());
+            if let Some(block) = state.commands.command_end(line, col, exit_code) {
+                state.emit(TermEvent::CommandFinished(block));
+            }
+            PromptMark::CommandEnd(exit_code)
+        }
+        _ => return,
+    };
+    grid.add_mark(state.cursor_row, mark);
+}
+
+/// Parse the URL of an OSC 7 report into a path
+///
+/// The host is not checked, so directories on remote hosts are reported too.
+fn parse_osc7(url: &str) -> Option<PathBuf> {
+    let rest = url
+        .strip_prefix("file://")
+        .or_else(|| url.strip_prefix("kitty-shell-cwd://"))?;
+    let path = &rest[rest.find('/')?..];
+    // Percent-decode the path
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+/// Handle a complete DCS sequence (payload in `escape_buf`)
+fn dispatch_dcs(state: &mut TermState) {
+    // DECRQSS - request selection or setting
+    if let Some(setting) = state.escape_buf.strip_prefix(b"$q") {
+        let value = match setting {
+            b"m" => Some(format!("{}m", sgr_report(state))),
+            b" q" => {
+                // Without an explicit DECSCUSR the default (steady block) is reported
+                let style = state.cursor_style(CursorStyle::default());
+                let n = match style.shape {
+                    CursorShape::Block => 1,
+                    CursorShape::Underline => 3,
+                    CursorShape::Bar => 5,
+                } + u16::from(!style.blinking);
+                Some(format!("{n} q"))
+            }
+            _ => None,
+        };
+        let reply = match value {
+            Some(value) => format!("\x1bP1$r{value}\x1b\\"),
+            None => "\x1bP0$r\x1b\\".to_string(),
+        };
+        state.reply(reply.as_bytes());
+    }
+}
+
+/// Current SGR attributes as a parameter string (for DECRQSS)
+fn sgr_report(state: &TermState) -> String {
+    let mut params = vec!["0".to_string()];
+    for (enabled, param) in [
+        (state.bold, "1"),
+        (state.dim, "2"),
+        (state.italic, "3"),
+        (state.underline, "4"),
+    ] {
+        if enabled {
+            params.push(param.to_string());
+        }
+    }
+    if state.fg != Color::default_fg() {
+        params.push(format!("38;2;{};{};{}", state.fg.r, state.fg.g, state.fg.b));
+    }
+    if state.bg != Color::default_bg() {
+        params.push(format!("48;2;{};{};{}", state.bg.r, state.bg.g, state.bg.b));
+    }
+    params.join(";")
+}
+
+/// Crate version as a single number (`1.2.3` -> 10203) for secondary DA
+fn version_number() -> u32 {
+    env!("CARGO_PKG_VERSION")
+        .split('.')
+        .take(3)
+        .map(|part| part.parse::<u32>().unwrap_or(0))
+        .fold(0, |acc, part| acc * 100 + part)
+}
+
+/// DECRQM mode value: 1 = set, 2 = reset, 0 = not recognized
+fn mode_report(value: Option<bool>) -> u8 {
+    match value {
+        Some(true) => 1,
+        Some(false) => 2,
+        None => 0,
+    }
+}
+
+/// Current value of a DEC private mode, or `None` if it isn't supported
+fn private_mode(state: &TermState, mode: u16) -> Option<bool> {
+    Some(match mode {
+        12 => state.cursor_blink.unwrap_or(false),
+        25 => state.cursor_visible,
+        1004 => state.focus_reporting,
+        m => return state.modes.private(m).or_else(|| state.mouse.mode(m)),
+    })
+}
+
+/// Set or reset a DEC private mode
+fn set_private_mode(state: &mut TermState, grid: &Grid, mode: u16, enabled: bool) {
+    match mode {
+        // Cursor blinking (att610)
+        12 => state.cursor_blink = Some(enabled),
+        // DECTCEM - cursor visibility
+        25 => state.cursor_visible = enabled,
+        // DECOM - origin mode, which homes the cursor to the top margin
+        6 => {
+            state.modes.origin = enabled;
+            cursor_position(state, grid, 1, 1);
+        }
+        // DECAWM, DECSCNM and synchronized output
+        m if state.modes.set_private(m, enabled) => {}
+        // Mouse tracking and encoding modes
+        m if state.mouse.set_mode(m, enabled) => state.mouse_cell = None,
+        // Focus in/out reporting
+        1004 => state.focus_reporting = enabled,
+        // Other modes - ignore for now
+        _ => {}
+    }
+}
+
+/// DECSTR - reset modes, margins, attributes, charsets and cursor style, leaving the screen alone
+fn soft_reset(state: &mut TermState) {
+    state.cursor_visible = true;
+    state.cursor_shape = None;
+    state.cursor_blink = None;
+    state.modes.insert = false;
+    state.modes.origin = false;
+    state.scroll_region = None;
+    state.modes.autowrap = true;
+    state.wrap_pending = false;
+    state.reset_attributes();
+    state.link = None;
+    state.charsets = Charsets::default();
+    state.saved_cursor = None;
+}
+
+/// RIS - return to the initial state: screen, modes, tab stops, colors and images
+///
+/// The title, working directory and command history are kept.
+fn full_reset(state: &mut TermState, grid: &mut Grid) {
+    soft_reset(state);
+    grid.clear();
+    grid.reset_tab_stops();
+    state.cursor_row = 0;
+    state.cursor_col = 0;
+    state.modes = TermModes::default();
+    state.keyboard = KeyboardModes::default();
+    state.mouse = MouseModes::default();
+    state.mouse_cell = None;
+    state.focus_reporting = false;
+    state.palette = state.base_palette;
+    state.color_overrides = [None; 3];
+    state.title_stack.clear();
+    // Images go with the screen, including those placed above it
+    let limit = state.images.limit;
+    state.images = ImageStore::default();
+    state.images.limit = limit;
+    state.sixel = None;
+    state.kitty_transfer = None;
+    state.iterm_upload = None;
+}
+
+/// Process SGR (Select Graphic Rendition) escape sequence
+fn process_sgr(state: &mut TermState) {
+    let params_str = String::from_utf8_lossy(&state.escape_buf);
+    let params: Vec<u8> = if params_str.is_empty() {
+        vec![0] // Default to reset
+    } else {
+        params_str
+            .split(';')
+            .filter_map(|s| s.parse().ok())
+            .collect()
+    };
+
+    let mut i = 0;
+    while i < params.len() {
+        match params[i] {
+            // Reset all attributes
+            0 => state.reset_attributes(),
+            1 => state.bold = true,
+            2 => state.dim = true,
+            3 => state.italic = true,
+            4 => state.underline = true,
+            22 => {
+                state.bold = false;
+                state.dim = false;
+            }
+            23 => state.italic = false,
+            24 => state.underline = false,
+            // Standard foreground colors (30-37)
+            n @ 30..=37 => state.fg = state.palette[usize::from(n - 30)],
+            39 => state.fg = Color::default_fg(), // Default fg
+            // Standard background colors (40-47)
+            n @ 40..=47 => state.bg = state.palette[usize::from(n - 40)],
+            49 => state.bg = Color::default_bg(), // Default bg
+            // Bright foreground colors (90-97)
+            n @ 90..=97 => state.fg = state.palette[usize::from(n - 90 + 8)],
+            // Bright background colors (100-107)
+            n @ 100..=107 => state.bg = state.palette[usize::from(n - 100 + 8)],
+            // 256-color mode (38;5;N or 48;5;N)
+            38 if i + 2 < params.len() && params[i + 1] == 5 => {
+                state.fg = state.palette[usize::from(params[i + 2])];
+                i += 2;
+            }
+            48 if i + 2 < params.len() && params[i + 1] == 5 => {
+                state.bg = state.palette[usize::from(params[i + 2])];
+                i += 2;
+            }
+            // Truecolor (38;2;R;G;B or 48;2;R;G;B)
+            38 if i + 4 < params.len() && params[i + 1] == 2 => {
+                state.fg = Color::new(params[i + 2], params[i + 3], params[i + 4]);
+                i += 4;
+            }
+            48 if i + 4 < params.len() && params[i + 1] == 2 => {
+                state.bg = Color::new(params[i + 2], params[i + 3], params[i + 4]);
+                i += 4;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// The standard 256-color palette
+fn default_palette() -> [Color; 256] {
+    std::array::from_fn(|i| color_from_256(u8::try_from(i).unwrap_or(u8::MAX)))
+}
+
+/// Convert 256-color palette index to RGB
+fn color_from_256(n: u8) -> Color {
+    match n {
+        // Standard colors (0-15)
+        0 => Color::new(0, 0, 0),
+        1 => Color::new(205, 49, 49),
+        2 => Color::new(13, 188, 121),
+        3 => Color::new(229, 229, 16),
+        4 => Color::new(36, 114, 200),
+        5 => Color::new(188, 63, 188),
+        6 => Color::new(17, 168, 205),
+        7 => Color::new(229, 229, 229),
+        8 => Color::new(102, 102, 102),
+        9 => Color::new(241, 76, 76),
+        10 => Color::new(35, 209, 139),
+        11 => Color::new(245, 245, 67),
+        12 => Color::new(59, 142, 234),
+        13 => Color::new(214, 112, 214),
+        14 => Color::new(41, 184, 219),
+        15 => Color::new(255, 255, 255),
+        // 216-color cube (16-231)
+        16..=231 => {
+            let n = n - 16;
+            let r = (n / 36) % 6;
+            let g = (n / 6) % 6;
+            let b = n % 6;
+            let to_255 = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            Color::new(to_255(r), to_255(g), to_255(b))
+        }
+        // Grayscale (232-255)
+        232..=255 => {
+            let gray = 8 + (n - 232) * 10;
+            Color::new(gray, gray, gray)
+        }
+    }
+}
+
+/// Scroll rows `top..=bottom` of the grid up by one line
+fn scroll_up(grid: &mut Grid, top: usize, bottom: usize, cols: usize) {
+    // Move the rows below the top up by one
+    for row in top + 1..=bottom {
+        for col in 0..cols {
+            if let Some(cell) = grid.get(row, col).cloned() {
+                grid.set(row - 1, col, cell);
+            }
+        }
+        grid.set_wrapped(row - 1, grid.is_wrapped(row));
+        grid.set_marks(row - 1, grid.marks(row).to_vec());
+        grid.set_line_size(row - 1, grid.line_size(row));
+    }
+    // Clear the bottom row
+    for col in 0..cols {
+        grid.set(bottom, col, Cell::default());
+    }
+    grid.set_wrapped(bottom, false);
+    grid.set_marks(bottom, Vec::new());
+    grid.set_line_size(bottom, LineSize::Single);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(state: &mut TermState, grid: &mut Grid, input: &[u8]) {
+        let (rows, cols) = (grid.rows(), grid.cols());
+        for &byte in input {
+            process_byte(state, grid, byte, rows, cols);
+        }
+    }
+
+    #[test]
+    fn test_print_and_newline() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"ab\r\ncd");
+        assert_eq!(grid.get(0, 0).unwrap().c, 'a');
+        assert_eq!(grid.get(1, 1).unwrap().c, 'd');
+        assert_eq!((state.cursor_row, state.cursor_col), (1, 2));
+    }
+
+    #[test]
+    fn test_csi_parse_prefix_and_params() {
+        let params = CsiParams::parse(b">5;1:2");
+        assert_eq!(params.prefix, Some(b'>'));
+        assert_eq!(params.params, vec![5, 1]);
+        assert_eq!(params.get(2, 7), 7);
+        assert_eq!(params.intermediate, None);
+
+        let params = CsiParams::parse(b"4 ");
+        assert_eq!(params.params, vec![4]);
+        assert_eq!(params.intermediate, Some(b' '));
+    }
+
+    #[test]
+    fn test_cursor_visibility_and_shape() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        let default = CursorStyle::new(CursorShape::Block, true);
+        feed(&mut state, &mut grid, b"\x1b[?25l");
+        assert!(!state.cursor_visible);
+        feed(&mut state, &mut grid, b"\x1b[?25h\x1b[6 q");
+        assert!(state.cursor_visible);
+        assert_eq!(
+            state.cursor_style(default),
+            CursorStyle::new(CursorShape::Bar, false)
+        );
+        feed(&mut state, &mut grid, b"\x1b[?12h");
+        assert!(state.cursor_style(default).blinking);
+        feed(&mut state, &mut grid, b"\x1b[0 q");
+        assert_eq!(state.cursor_style(default), default);
+    }
+
+    #[test]
+    fn test_csi_tilde_final_byte_ends_sequence() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[3~x");
+        assert_eq!(grid.get(0, 0).unwrap().c, 'x');
+    }
+
+    #[test]
+    fn test_sgr_256_color() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[38;5;196m");
+        assert_eq!(state.fg, color_from_256(196));
+    }
+
+    #[test]
+    fn test_kitty_keyboard_push_query_pop() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[>1u\x1b[>11u\x1b[?u");
+        assert_eq!(state.take_replies(), b"\x1b[?11u");
+        feed(&mut state, &mut grid, b"\x1b[<u\x1b[?u");
+        assert_eq!(state.take_replies(), b"\x1b[?1u");
+        feed(&mut state, &mut grid, b"\x1b[<5u\x1b[?u");
+        assert_eq!(state.take_replies(), b"\x1b[?0u");
+    }
+
+    #[test]
+    fn test_modify_other_keys_set_query_reset() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[>4;2m\x1b[?4m");
+        assert_eq!(state.keyboard.modify_other_keys(), 2);
+        assert_eq!(state.take_replies(), b"\x1b[>4;2m");
+        feed(&mut state, &mut grid, b"\x1b[>4m");
+        assert_eq!(state.keyboard.modify_other_keys(), 0);
+        feed(&mut state, &mut grid, b"\x1b[>4;1m\x1b[>4n");
+        assert_eq!(state.keyboard.modify_other_keys(), 0);
+        // Plain SGR is unaffected
+        assert_eq!(state.fg, Color::default_fg());
+    }
+
+    #[test]
+    fn test_mouse_modes() {
+        use crate::mouse::{MouseEncoding, MouseTracking};
+
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[?1000;1006h");
+        assert_eq!(state.mouse.tracking, MouseTracking::Normal);
+        assert_eq!(state.mouse.encoding, MouseEncoding::Sgr);
+        feed(&mut state, &mut grid, b"\x1b[?1000l");
+        assert!(!state.mouse.is_active());
+    }
+
+    #[test]
+    fn test_focus_reporting_mode() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[?1004h");
+        assert!(state.focus_reporting);
+        feed(&mut state, &mut grid, b"\x1b[?1004l");
+        assert!(!state.focus_reporting);
+    }
+
+    #[test]
+    fn test_cursor_position_report() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(5, 10);
+        feed(&mut state, &mut grid, b"ab\r\ncde\x1b[6n");
+        assert_eq!(state.take_replies(), b"\x1b[2;4R");
+        feed(&mut state, &mut grid, b"\x1b[?6n\x1b[5n");
+        assert_eq!(state.take_replies(), b"\x1b[?2;4R\x1b[0n");
+    }
+
+    #[test]
+    fn test_device_attributes() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(5, 10);
+        feed(&mut state, &mut grid, b"\x1b[c");
+        assert_eq!(state.take_replies(), b"\x1b[?62;4;22c");
+        feed(&mut state, &mut grid, b"\x1b[>c");
+        let reply = String::from_utf8(state.take_replies()).unwrap();
+        assert_eq!(reply, format!("\x1b[>1;{};0c", version_number()));
+        feed(&mut state, &mut grid, b"\x1b[>q");
+        let reply = String::from_utf8(state.take_replies()).unwrap();
+        assert!(reply.starts_with("\x1bP>|dioxus-terminal("));
+        assert!(reply.ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn test_decrqm() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(5, 10);
+        feed(&mut state, &mut grid, b"\x1b[?25$p\x1b[?1000$p\x1b[?9999$p");
+        assert_eq!(
+            state.take_replies(),
+            b"\x1b[?25;1$y\x1b[?1000;2$y\x1b[?9999;0$y"
+        );
+        feed(&mut state, &mut grid, b"\x1b[?1006h\x1b[?1006$p");
+        assert_eq!(state.take_replies(), b"\x1b[?1006;1$y");
+    }
+
+    #[test]
+    fn test_decrqss() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(5, 10);
+        feed(&mut state, &mut grid, b"\x1b[1;4m\x1bP$qm\x1b\\");
+        assert_eq!(state.take_replies(), b"\x1bP1$r0;1;4m\x1b\\");
+        feed(&mut state, &mut grid, b"\x1b[6 q\x1bP$q q\x1b\\");
+        assert_eq!(state.take_replies(), b"\x1bP1$r6 q\x1b\\");
+        feed(&mut state, &mut grid, b"\x1bP$qx\x1b\\");
+        assert_eq!(state.take_replies(), b"\x1bP0$r\x1b\\");
+    }
+
+    #[test]
+    fn test_decrqss_sgr_round_trip() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(5, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b[3;38;5;196;48;2;1;2;3m\x1bP$qm\x1b\\",
+        );
+        let reply = state.take_replies();
+        let (fg, bg) = (state.fg, state.bg);
+        assert_eq!(bg, Color::new(1, 2, 3));
+        // Replaying the reported attributes restores the same colors
+        let sgr = reply
+            .strip_prefix(b"\x1bP1$r")
+            .and_then(|rest| rest.strip_suffix(b"m\x1b\\"))
+            .unwrap()
+            .to_vec();
+        feed(&mut state, &mut grid, b"\x1b[0m\x1b[");
+        feed(&mut state, &mut grid, &sgr);
+        feed(&mut state, &mut grid, b"m");
+        assert_eq!((state.fg, state.bg), (fg, bg));
+        assert!(state.italic);
+    }
+
+    #[test]
+    fn test_version_number() {
+        assert!(version_number() > 0);
+    }
+
+    #[test]
+    fn test_osc_title() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b]0;vim\x07x");
+        assert_eq!(state.title, "vim");
+        assert_eq!(state.icon_name, "vim");
+        assert_eq!(grid.get(0, 0).unwrap().c, 'x');
+        feed(&mut state, &mut grid, b"\x1b]2;build \xe2\x9c\x93\x1b\\");
+        assert_eq!(state.title, "build \u{2713}");
+        assert_eq!(state.icon_name, "vim");
+        assert_eq!(
+            state.take_events(),
+            vec![
+                TermEvent::TitleChanged("vim".to_string()),
+                TermEvent::TitleChanged("build \u{2713}".to_string()),
+            ]
+        );
+        // Unchanged titles don't raise events
+        feed(&mut state, &mut grid, b"\x1b]2;build \xe2\x9c\x93\x07");
+        assert!(state.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_soft_wrap_marks_row() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 4);
+        feed(&mut state, &mut grid, b"abcdef\r\nxy");
+        assert!(grid.is_wrapped(0));
+        assert!(!grid.is_wrapped(1));
+        // Wrap flags scroll with their rows
+        let mut grid = Grid::new(2, 4);
+        state.cursor_row = 0;
+        state.cursor_col = 0;
+        feed(&mut state, &mut grid, b"abcdefghi");
+        assert_eq!(grid.get(0, 0).unwrap().c, 'e');
+        assert!(grid.is_wrapped(0));
+        assert!(!grid.is_wrapped(1));
+    }
+
+    #[test]
+    fn test_osc8_hyperlink() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"a\x1b]8;id=1;https://example.com\x1b\\bc\x1b]8;;\x1b\\d",
+        );
+        let link = Hyperlink::new(Some("1".to_string()), "https://example.com");
+        assert_eq!(grid.get(0, 0).unwrap().link, None);
+        assert_eq!(grid.get(0, 1).unwrap().link.as_deref(), Some(&link));
+        assert_eq!(grid.get(0, 2).unwrap().link.as_deref(), Some(&link));
+        assert_eq!(grid.get(0, 3).unwrap().link, None);
+        assert_eq!(state.link, None);
+    }
+
+    #[test]
+    fn test_osc52_clipboard() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]52;c;aGVsbG8=\x07\x1b]52;c;?\x1b\\",
+        );
+        assert_eq!(
+            state.take_events(),
+            vec![
+                TermEvent::ClipboardWrite("hello".to_string()),
+                TermEvent::ClipboardRead {
+                    selection: "c".to_string()
+                },
+            ]
+        );
+        // Payloads over the limit are dropped, even past the usual OSC length
+        state.clipboard_limit = 8192;
+        let mut seq = b"\x1b]52;c;".to_vec();
+        seq.extend(std::iter::repeat_n(b'A', 12000));
+        seq.push(0x07);
+        feed(&mut state, &mut grid, &seq);
+        assert!(state.take_events().is_empty());
+        let mut seq = b"\x1b]52;c;".to_vec();
+        seq.extend(std::iter::repeat_n(b'A', 8000));
+        seq.push(0x07);
+        feed(&mut state, &mut grid, &seq);
+        assert_eq!(state.take_events().len(), 1);
+    }
+
+    #[test]
+    fn test_osc_palette() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b]4;1;?\x07");
+        assert_eq!(state.take_replies(), b"\x1b]4;1;rgb:cdcd/3131/3131\x07");
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]4;1;#ff0000;200;rgb:00/00/ff\x1b\\\x1b[31mx",
+        );
+        assert_eq!(grid.get(0, 0).unwrap().fg, Color::new(255, 0, 0));
+        assert_eq!(state.palette[200], Color::new(0, 0, 255));
+        feed(&mut state, &mut grid, b"\x1b]104;1\x07");
+        assert_eq!(state.palette[1], Color::new(205, 49, 49));
+        assert_eq!(state.palette[200], Color::new(0, 0, 255));
+        feed(&mut state, &mut grid, b"\x1b]104\x07");
+        assert_eq!(state.palette[200], color_from_256(200));
+    }
+
+    #[test]
+    fn test_osc_palette_reset_to_theme() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        let mut ansi = [Color::new(0, 0, 0); 16];
+        ansi[1] = Color::new(1, 1, 1);
+        ansi[2] = Color::new(2, 2, 2);
+        feed(&mut state, &mut grid, b"\x1b]4;2;#ff0000\x07");
+        // A theme change keeps colors the application set
+        state.set_theme_palette(Some(ansi));
+        assert_eq!(state.palette[1], Color::new(1, 1, 1));
+        assert_eq!(state.palette[2], Color::new(255, 0, 0));
+        feed(&mut state, &mut grid, b"\x1b]104;2\x07");
+        assert_eq!(state.palette[2], Color::new(2, 2, 2));
+        feed(&mut state, &mut grid, b"\x1b]4;1;#ff0000\x07\x1bc");
+        assert_eq!(state.palette[1], Color::new(1, 1, 1));
+        assert_eq!(state.palette[200], color_from_256(200));
+    }
+
+    #[test]
+    fn test_osc_dynamic_colors() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        state.theme_colors = [
+            Color::new(1, 2, 3),
+            Color::new(4, 5, 6),
+            Color::new(7, 8, 9),
+        ];
+        feed(&mut state, &mut grid, b"\x1b]11;?\x1b\\");
+        assert_eq!(state.take_replies(), b"\x1b]11;rgb:0404/0505/0606\x1b\\");
+        // OSC 10 with two specs sets foreground and background
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]10;#ffffff;#000000\x07\x1b]12;rgb:ff/00/00\x07",
+        );
+        assert_eq!(
+            state.color_overrides,
+            [
+                Some(Color::new(255, 255, 255)),
+                Some(Color::new(0, 0, 0)),
+                Some(Color::new(255, 0, 0)),
+            ]
+        );
+        feed(&mut state, &mut grid, b"\x1b]10;?\x07");
+        assert_eq!(state.take_replies(), b"\x1b]10;rgb:ffff/ffff/ffff\x07");
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]110\x07\x1b]111\x07\x1b]112\x07",
+        );
+        assert_eq!(state.color_overrides, [None; 3]);
+        // Reset colors are the theme's again
+        feed(&mut state, &mut grid, b"\x1b]11;?\x07");
+        assert_eq!(state.take_replies(), b"\x1b]11;rgb:0404/0505/0606\x07");
+    }
+
+    #[test]
+    fn test_osc7_cwd() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]7;file://myhost/home/me/my%20project\x07",
+        );
+        let cwd = PathBuf::from("/home/me/my project");
+        assert_eq!(state.cwd.as_ref(), Some(&cwd));
+        assert!(state.cwd_reported);
+        assert_eq!(state.take_events(), vec![TermEvent::CwdChanged(cwd)]);
+        // Same directory again doesn't raise an event
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]7;file:///home/me/my%20project\x07",
+        );
+        assert!(state.take_events().is_empty());
+        assert_eq!(parse_osc7("file://host"), None);
+        assert_eq!(parse_osc7("file:///bad%2"), None);
+        assert_eq!(parse_osc7("http://host/x"), None);
+    }
+
+    #[test]
+    fn test_osc133_command_blocks() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07a\r\nb\r\n\x1b]133;D;2\x07",
+        );
+        // The prompt line has scrolled off, but block lines stay absolute
+        assert_eq!(state.scrolled_lines, 1);
+        let block = &state.commands.blocks()[0];
+        assert_eq!(block.prompt_line, 0);
+        assert_eq!(block.command, "ls");
+        assert_eq!(block.output, Some(1..3));
+        assert_eq!(block.exit_code, Some(2));
+        assert!(block.failed());
+        // Marks scroll with their rows
+        assert_eq!(grid.marks(0), [PromptMark::OutputStart]);
+        assert_eq!(grid.marks(2), [PromptMark::CommandEnd(Some(2))]);
+    }
+
+    #[test]
+    fn test_notifications() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]9;done\x07\x1b]777;notify;make;ok\x1b\\\x1b]9;4;1;50\x07",
+        );
+        assert_eq!(
+            state.take_events(),
+            vec![
+                TermEvent::Notification(Notification::new(None, "done")),
+                TermEvent::Notification(Notification::new(Some("make".to_string()), "ok")),
+            ]
+        );
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]133;A\x07\x1b]133;B\x07\x1b]133;C\x07\x1b]133;D;0\x07",
+        );
+        let events = state.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [TermEvent::CommandFinished(block)] if block.exit_code == Some(0)
+        ));
+        // The shell exiting mid-command finishes it once; an idle prompt doesn't
+        let run_time = Duration::from_secs(5);
+        feed(&mut state, &mut grid, b"\x1b]133;A\x07\x1b]133;B\x07");
+        state.child_exited("bash", Some(0), run_time);
+        assert!(state.take_events().is_empty());
+        feed(&mut state, &mut grid, b"\x1b]133;C\x07");
+        state.child_exited("bash", Some(0), run_time);
+        state.child_exited("bash", Some(0), run_time);
+        let events = state.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [TermEvent::CommandFinished(block)] if block.exit_code.is_none()
+        ));
+        // Without shell integration the process itself finished
+        let mut state = TermState::new(None);
+        state.child_exited("make", Some(2), run_time);
+        let events = state.take_events();
+        assert!(matches!(
+            events.as_slice(),
+            [TermEvent::CommandFinished(block)]
+                if block.command == "make" && block.failed() && block.duration == Some(run_time)
+        ));
+    }
+
+    #[test]
+    fn test_title_stack() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b]0;shell\x07\x1b[22;0t\x1b]0;vim\x07",
+        );
+        assert_eq!(state.title, "vim");
+        feed(&mut state, &mut grid, b"\x1b[23;2t");
+        assert_eq!(state.title, "shell");
+        assert_eq!(state.icon_name, "vim");
+        assert_eq!(
+            state.take_events().last(),
+            Some(&TermEvent::TitleChanged("shell".to_string()))
+        );
+        // Popping an empty stack is harmless
+        feed(&mut state, &mut grid, b"\x1b[23;0t");
+        assert_eq!(state.title, "shell");
+    }
+
+    #[test]
+    fn test_sixel_image() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(4, 10);
+        // 2x18 pixels: three bands, two rows of 16px cells
+        feed(&mut state, &mut grid, b"ab\x1bPq#1!2~-!2~-!2~\x1b\\x");
+        let images = state.images.visible(state.scrolled_lines, 4);
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].row, images[0].col), (0, 2));
+        assert_eq!((images[0].width, images[0].height), (2, 18));
+        // Text continues below the image; the Sixel data is not printed
+        assert_eq!((state.cursor_row, state.cursor_col), (2, 3));
+        assert_eq!(grid.get(2, 2).unwrap().c, 'x');
+        assert_eq!(grid.get(0, 2).unwrap().c, ' ');
+        // The image scrolls with the text and is dropped once off screen
+        feed(&mut state, &mut grid, b"\n\n");
+        assert_eq!(state.images.visible(state.scrolled_lines, 4)[0].row, -1);
+        feed(&mut state, &mut grid, b"\n");
+        assert!(state.images.visible(state.scrolled_lines, 4).is_empty());
+    }
+
+    #[test]
+    fn test_kitty_graphics() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(4, 10);
+        // 2x1 RGB image in two chunks, shown over 2x2 cells of 8x16 pixels
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b_Ga=T,f=24,s=2,v=1,i=7,c=2,r=2,z=-1,m=1;AQID\x1b\\\x1b_Gm=0;BAUG\x1b\\x",
+        );
+        assert_eq!(state.take_replies(), b"\x1b_Gi=7;OK\x1b\\");
+        let images = state.images.visible(0, 4);
+        assert_eq!(images.len(), 1);
+        assert_eq!(
+            (images[0].width, images[0].height, images[0].z),
+            (16, 32, -1)
+        );
+        // The cursor ends after the image on its last row; APC data is not printed
+        assert_eq!(grid.get(1, 2).unwrap().c, 'x');
+        assert_eq!(grid.get(0, 0).unwrap().c, ' ');
+
+        // Placing it again, then deleting placements keeps the image data
+        feed(&mut state, &mut grid, b"\x1b_Ga=p,i=7,p=3,C=1\x1b\\");
+        assert_eq!(state.take_replies(), b"\x1b_Gi=7,p=3;OK\x1b\\");
+        assert_eq!(state.images.visible(0, 4).len(), 2);
+        feed(&mut state, &mut grid, b"\x1b_Ga=d,d=i,i=7\x1b\\");
+        assert!(state.images.visible(0, 4).is_empty());
+        assert!(state.images.find(7).is_some());
+        feed(&mut state, &mut grid, b"\x1b_Ga=d,d=I,i=7\x1b\\");
+        assert!(state.images.find(7).is_none());
+        feed(&mut state, &mut grid, b"\x1b_Ga=p,i=7\x1b\\");
+        assert_eq!(
+            state.take_replies(),
+            b"\x1b_Gi=7;ENOENT:no such image\x1b\\"
+        );
+
+        // Queries check the data without storing it
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b_Ga=q,i=31,s=1,v=1,f=24;AAAA\x1b\\",
+        );
+        assert_eq!(state.take_replies(), b"\x1b_Gi=31;OK\x1b\\");
+        assert!(state.images.find(31).is_none());
+    }
+
+    #[test]
+    fn test_iterm2_inline_image() {
+        let url = Image::new(16, 8, vec![0; 16 * 8 * 4])
+            .to_data_url()
+            .unwrap();
+        let png = &url["data:image/png;base64,".len()..];
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(4, 10);
+        // Two cells wide keeps the aspect ratio: 16x8 pixels, one row of 16px cells
+        let file = format!("\x1b]1337;File=inline=1;width=2:{png}\x07x");
+        feed(&mut state, &mut grid, file.as_bytes());
+        let images = state.images.visible(0, 4);
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].width, images[0].height), (16, 8));
+        assert_eq!(grid.get(0, 2).unwrap().c, 'x');
+
+        // Sent in parts; downloads (no `inline=1`) are ignored
+        let (first, second) = png.split_at(10);
+        let parts = format!(
+            "\x1b]1337;MultipartFile=inline=1;height=2\x07\x1b]1337;FilePart={first}\x07\x1b]1337;FilePart={second}\x07\x1b]1337;FileEnd\x07"
+        );
+        feed(&mut state, &mut grid, parts.as_bytes());
+        let images = state.images.visible(0, 4);
+        assert_eq!((images[1].width, images[1].height), (64, 32));
+        feed(
+            &mut state,
+            &mut grid,
+            format!("\x1b]1337;File=:{png}\x07").as_bytes(),
+        );
+        assert_eq!(state.images.visible(0, 4).len(), 2);
+
+        // The data URL kept for display counts against the memory limit
+        let mut state = TermState::new(None);
+        state.images.limit = 2 * 16 * 8 * 4 + url.len();
+        let file = format!("\x1b]1337;File=inline=1:{png}\x07");
+        feed(&mut state, &mut grid, format!("{file}{file}").as_bytes());
+        assert_eq!(state.images.visible(0, 4).len(), 1);
+    }
+
+    #[test]
+    fn test_dec_line_drawing() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        // G0 designated directly, then G1 through SO/SI
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b(0lqk\x1b(B q\r\n\x1b)0\x0emj\x0fx",
+        );
+        let row = |grid: &Grid, row: usize| -> String {
+            (0..5).map(|col| grid.get(row, col).unwrap().c).collect()
+        };
+        assert_eq!(row(&grid, 0), "┌─┐ q");
+        assert_eq!(row(&grid, 1), "└┘x  ");
+        // Other charsets with intermediates are accepted and fall back to ASCII
+        feed(&mut state, &mut grid, b"\x1b(%5q");
+        assert_eq!(grid.get(1, 3).unwrap().c, 'q');
+    }
+
+    #[test]
+    fn test_pending_wrap() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(2, 4);
+        // Filling the last row leaves the cursor on the last column without scrolling
+        feed(&mut state, &mut grid, b"\nabcd");
+        assert_eq!((state.cursor_row, state.cursor_col), (1, 3));
+        assert_eq!(grid.get(1, 3).unwrap().c, 'd');
+        assert_eq!(state.scrolled_lines, 0);
+        // A carriage return cancels the wrap
+        feed(&mut state, &mut grid, b"\rx");
+        assert_eq!(grid.get(1, 0).unwrap().c, 'x');
+        assert_eq!(state.scrolled_lines, 0);
+        // The next character wraps and scrolls
+        feed(&mut state, &mut grid, b"yzwv");
+        assert_eq!(state.scrolled_lines, 1);
+        assert_eq!(grid.get(1, 0).unwrap().c, 'v');
+    }
+
+    #[test]
+    fn test_autowrap_and_insert_modes() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(2, 4);
+        // Without autowrap the last column is overwritten
+        feed(&mut state, &mut grid, b"\x1b[?7labcdef");
+        assert_eq!(grid.get(0, 3).unwrap().c, 'f');
+        assert_eq!((state.cursor_row, state.cursor_col), (0, 3));
+        feed(&mut state, &mut grid, b"\x1b[?7$p");
+        assert_eq!(state.take_replies(), b"\x1b[?7;2$y");
+        // Insert mode shifts the rest of the line right
+        feed(&mut state, &mut grid, b"\x1b[?7h\x1b[4h\rXY\x1b[4l");
+        let row: String = (0..4).map(|col| grid.get(0, col).unwrap().c).collect();
+        assert_eq!(row, "XYab");
+        feed(&mut state, &mut grid, b"\x1b[4$p");
+        assert_eq!(state.take_replies(), b"\x1b[4;2$y");
+    }
+
+    #[test]
+    fn test_newline_and_screen_modes() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"ab\ncd");
+        assert_eq!(grid.get(1, 2).unwrap().c, 'c');
+        // LNM: line feed also returns the carriage
+        feed(&mut state, &mut grid, b"\x1b[20h\nef");
+        assert_eq!(grid.get(2, 0).unwrap().c, 'e');
+        feed(&mut state, &mut grid, b"\x1b[?5h");
+        assert!(state.modes.reverse_video);
+        // DECOM homes the cursor
+        feed(&mut state, &mut grid, b"\x1b[?6h");
+        assert!(state.modes.origin);
+        assert_eq!((state.cursor_row, state.cursor_col), (0, 0));
+    }
+
+    #[test]
+    fn test_origin_mode_and_margins() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(5, 10);
+        // CUP is 1-based and clamped to the screen
+        feed(&mut state, &mut grid, b"\x1b[5;1Hz\x1b[9;20H");
+        assert_eq!((state.cursor_row, state.cursor_col), (4, 9));
+        // DECSTBM homes the cursor; DECOM addresses rows from the top margin
+        feed(&mut state, &mut grid, b"\x1b[2;4r");
+        assert_eq!((state.cursor_row, state.cursor_col), (0, 0));
+        feed(&mut state, &mut grid, b"\x1b[?6h");
+        assert_eq!((state.cursor_row, state.cursor_col), (1, 0));
+        feed(&mut state, &mut grid, b"x\x1b[2;1Hy\x1b[9;3H\x1b[6n");
+        assert_eq!(grid.get(1, 0).unwrap().c, 'x');
+        assert_eq!(grid.get(2, 0).unwrap().c, 'y');
+        assert_eq!((state.cursor_row, state.cursor_col), (3, 2));
+        assert_eq!(state.take_replies(), b"\x1b[3;3R");
+        // A line feed at the bottom margin scrolls only the region
+        feed(&mut state, &mut grid, b"\n");
+        assert_eq!(grid.get(1, 0).unwrap().c, 'y');
+        assert_eq!(grid.get(4, 0).unwrap().c, 'z');
+        assert_eq!(state.scrolled_lines, 0);
+        // DECSC keeps origin mode; DECSTR resets it with the margins
+        feed(&mut state, &mut grid, b"\x1b7\x1b[?6l\x1b8");
+        assert!(state.modes.origin);
+        feed(&mut state, &mut grid, b"\x1b[!p");
+        assert!(!state.modes.origin);
+        assert_eq!(state.margins(5), (0, 4));
+    }
+
+    #[test]
+    fn test_save_restore_cursor() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"ab\x1b[1;31m\x1b(0\x1b7\r\n\x1b[0m\x1b(Bcd",
+        );
+        assert_eq!((state.cursor_row, state.cursor_col), (1, 2));
+        // ESC 8 brings back the position, colors and line drawing set
+        feed(&mut state, &mut grid, b"\x1b8q");
+        let cell = grid.get(0, 2).unwrap();
+        assert_eq!(cell.c, '─');
+        assert!(cell.style.bold);
+        assert_eq!(cell.fg, state.palette[1]);
+        // CSI s / CSI u save only the latest cursor
+        feed(&mut state, &mut grid, b"\r\n\x1b[s\n\x1b[uA");
+        assert_eq!(grid.get(1, 0).unwrap().c, 'A');
+        // Restoring without a save homes the cursor
+        let mut state = TermState::new(None);
+        feed(&mut state, &mut grid, b"\n\n\x1b8");
+        assert_eq!((state.cursor_row, state.cursor_col), (0, 0));
+    }
+
+    #[test]
+    fn test_soft_and_full_reset() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 20);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b[?25l\x1b[4h\x1b[?7l\x1b[31mab\x1b]4;1;rgb:00/00/00\x07\x1b[3g\x1b[?1000h\x1b[5 q",
+        );
+        // DECSTR restores modes and attributes but keeps the screen
+        feed(&mut state, &mut grid, b"\x1b[!p");
+        assert!(state.cursor_visible);
+        assert_eq!((state.cursor_shape, state.cursor_blink), (None, None));
+        assert!(state.modes.autowrap && !state.modes.insert);
+        assert_eq!(state.fg, Color::default_fg());
+        assert_eq!(grid.get(0, 0).unwrap().c, 'a');
+        assert!(state.mouse.is_active());
+        // RIS clears the screen, tab stops, palette and mouse modes too
+        feed(&mut state, &mut grid, b"\x1bc");
+        assert_eq!(grid.get(0, 0).unwrap().c, ' ');
+        assert_eq!((state.cursor_row, state.cursor_col), (0, 0));
+        assert_eq!(grid.next_tab_stop(0), 8);
+        assert_eq!(state.palette, state.base_palette);
+        assert!(!state.mouse.is_active());
+    }
+
+    #[test]
+    fn test_synchronized_output_mode() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b[?2026$p\x1b[?2026h\x1b[?2026$p",
+        );
+        assert!(state.modes.synchronized);
+        assert_eq!(state.take_replies(), b"\x1b[?2026;2$y\x1b[?2026;1$y");
+        feed(&mut state, &mut grid, b"\x1b[?2026l");
+        assert!(!state.modes.synchronized);
+    }
+
+    #[test]
+    fn test_bell_rate_limit() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        // A flood of bells is reported once
+        feed(&mut state, &mut grid, &[0x07; 100]);
+        assert_eq!(state.take_events(), [TermEvent::Bell]);
+        // BEL ending an OSC sequence isn't a bell
+        state.last_bell = None;
+        feed(&mut state, &mut grid, b"\x1b]2;title\x07");
+        assert_eq!(
+            state.take_events(),
+            [TermEvent::TitleChanged("title".into())]
+        );
+        feed(&mut state, &mut grid, b"\x07");
+        assert_eq!(state.take_events(), [TermEvent::Bell]);
+    }
+
+    #[test]
+    fn test_line_sizes() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(
+            &mut state,
+            &mut grid,
+            b"\x1b#3Big\r\n\x1b#4Big\r\n\x1b#6abcdefg",
+        );
+        // Double-width rows hold half the columns, so the text wraps after 5;
+        // line sizes scroll with their rows
+        assert_eq!(state.scrolled_lines, 1);
+        assert_eq!(grid.line_size(0), LineSize::DoubleHeightBottom);
+        assert_eq!(grid.line_size(1), LineSize::DoubleWidth);
+        assert_eq!(grid.get(1, 4).unwrap().c, 'e');
+        assert_eq!(grid.get(2, 0).unwrap().c, 'f');
+        assert_eq!(grid.line_size(2), LineSize::Single);
+        // Making a row double-width keeps the cursor within its left half
+        feed(&mut state, &mut grid, b"hijk\x1b#6");
+        assert_eq!(grid.line_size(2), LineSize::DoubleWidth);
+        assert_eq!(state.cursor_col, 4);
+        feed(&mut state, &mut grid, b"\x1b#5");
+        assert_eq!(grid.line_size(2), LineSize::Single);
+    }
+
+    #[test]
+    fn test_screen_alignment() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(2, 3);
+        feed(&mut state, &mut grid, b"\x1b#6ab\x1b#8");
+        assert!(grid.iter_rows().flatten().all(|cell| cell.c == 'E'));
+        assert_eq!(grid.line_size(0), LineSize::Single);
+        assert_eq!((state.cursor_row, state.cursor_col), (0, 0));
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 30);
+        feed(&mut state, &mut grid, b"\tA");
+        assert_eq!(grid.get(0, 8).unwrap().c, 'A');
+        // HTS at column 3, then TBC clears the stop at column 8
+        state.cursor_col = 3;
+        feed(&mut state, &mut grid, b"\x1bH\r\tB");
+        assert_eq!(state.cursor_col, 4);
+        state.cursor_col = 8;
+        feed(&mut state, &mut grid, b"\x1b[g\r\t\tC");
+        assert_eq!(grid.get(0, 16).unwrap().c, 'C');
+        // CHT and CBT move by several stops
+        feed(&mut state, &mut grid, b"\r\x1b[2I");
+        assert_eq!(state.cursor_col, 16);
+        feed(&mut state, &mut grid, b"\x1b[Z");
+        assert_eq!(state.cursor_col, 3);
+        // With no stops left, HT goes to the last column
+        feed(&mut state, &mut grid, b"\x1b[3g\r\t");
+        assert_eq!(state.cursor_col, 29);
+    }
+
+    #[test]
+    fn test_kitty_keyboard_set_modes() {
+        let mut state = TermState::new(None);
+        let mut grid = Grid::new(3, 10);
+        feed(&mut state, &mut grid, b"\x1b[=3u");
+        assert_eq!(state.keyboard.kitty_flags(), 3);
+        feed(&mut state, &mut grid, b"\x1b[=8;2u");
+        assert_eq!(state.keyboard.kitty_flags(), 11);
+        feed(&mut state, &mut grid, b"\x1b[=2;3u");
+        assert_eq!(state.keyboard.kitty_flags(), 9);
+    }
+}
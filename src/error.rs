@@ -24,6 +24,10 @@ pub enum Error {
     /// Command not found
     #[error("command not found: {0}")]
     CommandNotFound(String),
+
+    /// Invalid link detection pattern
+    #[error("invalid link pattern: {0}")]
+    LinkPattern(String),
 }
 
 #[cfg(test)]
@@ -51,6 +55,12 @@ mod tests {
         assert_eq!(err.to_string(), "invalid terminal size: 0x80");
     }
 
+    #[test]
+    fn error_display_link_pattern() {
+        let err = Error::LinkPattern("unclosed group".to_string());
+        assert_eq!(err.to_string(), "invalid link pattern: unclosed group");
+    }
+
     #[test]
     fn error_display_command_not_found() {
         let err = Error::CommandNotFound("zsh".to_string());
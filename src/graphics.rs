@@ -0,0 +1,527 @@
+//! Inline images placed on the grid (Sixel, kitty graphics, iTerm2 images)
+//!
+//! Images are anchored to absolute lines, so they scroll with the text around
+//! them. Each terminal keeps images up to a memory limit, dropping the oldest.
+
+use base64::Engine;
+use image::ImageEncoder;
+use std::sync::Arc;
+
+/// Default image memory per terminal, in bytes of decoded RGBA and data URLs (64 MiB)
+pub const DEFAULT_IMAGE_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// A decoded image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels as RGBA, row by row
+    pub rgba: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, rgba: Vec<u8>) -> Self {
+        Self {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    /// The `width` x `height` region at `(x, y)`, clipped to the image
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in y..y + height {
+            let start = (row as usize * self.width as usize + x as usize) * 4;
+            rgba.extend_from_slice(&self.rgba[start..start + width as usize * 4]);
+        }
+        Self::new(width, height, rgba)
+    }
+
+    /// Decode a PNG, JPEG or GIF file (its first frame) within `max_bytes` of RGBA
+    pub fn decode(data: &[u8], max_bytes: usize) -> Result<Self, DecodeError> {
+        let mut reader = image::ImageReader::new(std::io::Cursor::new(data))
+            .with_guessed_format()
+            .map_err(|_| DecodeError::Invalid)?;
+        let mut limits = image::Limits::default();
+        limits.max_alloc = Some(max_bytes as u64);
+        reader.limits(limits);
+        let decoded = reader.decode().map_err(|e| match e {
+            image::ImageError::Limits(_) => DecodeError::TooLarge,
+            _ => DecodeError::Invalid,
+        })?;
+        let rgba = decoded.into_rgba8();
+        if rgba.len() > max_bytes {
+            return Err(DecodeError::TooLarge);
+        }
+        Ok(Self::new(rgba.width(), rgba.height(), rgba.into_raw()))
+    }
+
+    /// Encode as a `data:image/png` URL for the webview
+    pub fn to_data_url(&self) -> Option<String> {
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png)
+            .write_image(
+                &self.rgba,
+                self.width,
+                self.height,
+                image::ExtendedColorType::Rgba8,
+            )
+            .ok()?;
+        Some(format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(&png)
+        ))
+    }
+}
+
+/// Why an image file could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DecodeError {
+    /// Not a supported image format, or corrupt
+    Invalid,
+    /// Decoding would exceed the memory limit
+    TooLarge,
+}
+
+/// A stored image, addressable by its protocol id or number
+#[derive(Debug)]
+struct StoredImage {
+    key: u64,
+    // Kitty image id and number; Sixel images have neither
+    id: Option<u32>,
+    number: Option<u32>,
+    image: Image,
+    url: Arc<str>,
+}
+
+impl StoredImage {
+    /// Memory kept for the image: its pixels and its data URL
+    fn size(&self) -> usize {
+        self.image.rgba.len() + self.url.len()
+    }
+}
+
+/// An image shown at an absolute line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Placement {
+    /// Kitty placement id (0 if none was given)
+    pub id: u32,
+    pub line: usize,
+    pub col: usize,
+    /// Cells covered
+    pub rows: usize,
+    pub cols: usize,
+    /// Displayed size in pixels
+    pub width: u32,
+    pub height: u32,
+    /// Offset from the top-left corner of the first cell, in pixels
+    pub offset: (u32, u32),
+    /// Stacking order; negative values are drawn below text
+    pub z: i32,
+    pub url: Arc<str>,
+}
+
+impl Placement {
+    /// Placement at absolute `line` and `col`, displayed `width` x `height` pixels
+    ///
+    /// The cells covered are worked out from `cell_size` (in pixels).
+    pub fn new(
+        line: usize,
+        col: usize,
+        size: (u32, u32),
+        cell_size: (u32, u32),
+        url: Arc<str>,
+    ) -> Self {
+        let (width, height) = size;
+        Self {
+            id: 0,
+            line,
+            col,
+            rows: height.div_ceil(cell_size.1.max(1)).max(1) as usize,
+            cols: width.div_ceil(cell_size.0.max(1)).max(1) as usize,
+            width,
+            height,
+            offset: (0, 0),
+            z: 0,
+            url,
+        }
+    }
+
+    /// Whether the placement covers the cell at absolute `line` and `col`
+    pub fn covers(&self, line: usize, col: usize) -> bool {
+        (self.line..self.line + self.rows).contains(&line)
+            && (self.col..self.col + self.cols).contains(&col)
+    }
+}
+
+/// An image on screen, for rendering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ImageView {
+    pub id: u64,
+    /// Top row; negative when the image is partly scrolled off
+    pub row: isize,
+    pub col: usize,
+    /// Size and offset within the first cell, in pixels
+    pub width: u32,
+    pub height: u32,
+    pub offset: (u32, u32),
+    pub z: i32,
+    pub url: Arc<str>,
+}
+
+/// Images and their placements on a terminal's grid
+#[derive(Debug)]
+pub(crate) struct ImageStore {
+    images: Vec<StoredImage>,
+    // Placements with the key of their image and a unique view id
+    placements: Vec<(u64, u64, Placement)>,
+    next_key: u64,
+    /// Most image memory kept (decoded pixels and data URLs), in bytes
+    pub limit: usize,
+}
+
+impl Default for ImageStore {
+    fn default() -> Self {
+        Self {
+            images: Vec::new(),
+            placements: Vec::new(),
+            next_key: 1,
+            limit: DEFAULT_IMAGE_MEMORY_LIMIT,
+        }
+    }
+}
+
+impl ImageStore {
+    /// Store `image`, replacing any image with the same id
+    ///
+    /// Older images are dropped to stay within the limit. Returns the image's
+    /// key, or `None` if the image alone exceeds the limit.
+    pub fn add(&mut self, image: Image, id: Option<u32>, number: Option<u32>) -> Option<u64> {
+        if image.rgba.len() > self.limit {
+            return None;
+        }
+        let url: Arc<str> = image.to_data_url()?.into();
+        let bytes = image.rgba.len() + url.len();
+        if bytes > self.limit {
+            return None;
+        }
+        if let Some(key) = id.and_then(|id| self.find(id)) {
+            self.remove(key);
+        }
+        while self.used() + bytes > self.limit && !self.images.is_empty() {
+            self.remove(self.images[0].key);
+        }
+        let key = self.next_key;
+        self.next_key += 1;
+        self.images.push(StoredImage {
+            key,
+            id,
+            number,
+            image,
+            url,
+        });
+        Some(key)
+    }
+
+    /// Memory used by the images and by placements with their own (cropped) data URL
+    fn used(&self) -> usize {
+        let images: usize = self.images.iter().map(StoredImage::size).sum();
+        let crops: usize = self
+            .placements
+            .iter()
+            .filter(|(key, _, p)| self.owns_url(*key, p))
+            .map(|(_, _, p)| p.url.len())
+            .sum();
+        images + crops
+    }
+
+    /// Whether `placement` of image `key` has a data URL of its own
+    fn owns_url(&self, key: u64, placement: &Placement) -> bool {
+        self.get(key)
+            .is_none_or(|stored| !Arc::ptr_eq(&stored.url, &placement.url))
+    }
+
+    /// Key of the image with kitty id `id`
+    pub fn find(&self, id: u32) -> Option<u64> {
+        self.images
+            .iter()
+            .find(|stored| stored.id == Some(id))
+            .map(|stored| stored.key)
+    }
+
+    /// Key of the newest image with kitty number `number`
+    pub fn find_number(&self, number: u32) -> Option<u64> {
+        self.images
+            .iter()
+            .rev()
+            .find(|stored| stored.number == Some(number))
+            .map(|stored| stored.key)
+    }
+
+    /// Id of the image with `key`
+    pub fn id(&self, key: u64) -> Option<u32> {
+        self.get(key).and_then(|stored| stored.id)
+    }
+
+    /// Smallest id not used by any image
+    pub fn free_id(&self) -> u32 {
+        (1..=u32::MAX)
+            .find(|&id| self.find(id).is_none())
+            .unwrap_or(u32::MAX)
+    }
+
+    /// The image with `key`
+    pub fn image(&self, key: u64) -> Option<&Image> {
+        self.get(key).map(|stored| &stored.image)
+    }
+
+    /// Data URL of the whole image with `key`
+    pub fn url(&self, key: u64) -> Option<Arc<str>> {
+        self.get(key).map(|stored| stored.url.clone())
+    }
+
+    /// Show the image with `key`, replacing its placement with the same non-zero id
+    ///
+    /// Older images are dropped to make room for a cropped placement's data URL.
+    /// Returns `false` if it doesn't fit even then.
+    pub fn place(&mut self, key: u64, placement: Placement) -> bool {
+        if placement.id != 0 {
+            self.placements
+                .retain(|(image, _, p)| *image != key || p.id != placement.id);
+        }
+        if self.owns_url(key, &placement) {
+            let bytes = placement.url.len();
+            while self.used() + bytes > self.limit {
+                let Some(oldest) = self.images.iter().map(|s| s.key).find(|&k| k != key) else {
+                    break;
+                };
+                self.remove(oldest);
+            }
+            if self.used() + bytes > self.limit {
+                return false;
+            }
+        }
+        let view = self.next_key;
+        self.next_key += 1;
+        self.placements.push((key, view, placement));
+        true
+    }
+
+    /// Delete placements matching `filter` (given the image id and placement)
+    ///
+    /// With `free`, images left without placements are deleted too.
+    pub fn delete(&mut self, free: bool, filter: impl Fn(Option<u32>, &Placement) -> bool) {
+        let mut affected = Vec::new();
+        let images = &self.images;
+        self.placements.retain(|(key, _, p)| {
+            let id = images.iter().find(|s| s.key == *key).and_then(|s| s.id);
+            let delete = filter(id, p);
+            if delete {
+                affected.push(*key);
+            }
+            !delete
+        });
+        if free {
+            for key in affected {
+                if !self.placements.iter().any(|(image, _, _)| *image == key) {
+                    self.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Delete the image with `key` and its placements
+    pub fn remove(&mut self, key: u64) {
+        if let Some(index) = self.images.iter().position(|s| s.key == key) {
+            self.images.remove(index);
+        }
+        self.placements.retain(|(image, _, _)| *image != key);
+    }
+
+    /// Drop placements that have scrolled entirely above line `top`
+    ///
+    /// Images without an id (Sixel) go with their last placement; kitty images
+    /// stay until deleted, as they can be placed again.
+    pub fn prune(&mut self, top: usize) {
+        let mut dropped = Vec::new();
+        self.placements.retain(|(key, _, p)| {
+            let keep = p.line + p.rows > top;
+            if !keep {
+                dropped.push(*key);
+            }
+            keep
+        });
+        for key in dropped {
+            let anonymous = self.get(key).is_some_and(|stored| stored.id.is_none());
+            if anonymous && !self.placements.iter().any(|(image, _, _)| *image == key) {
+                self.remove(key);
+            }
+        }
+    }
+
+    /// Images visible with line `top` at row 0 of a grid of `rows` rows
+    pub fn visible(&self, top: usize, rows: usize) -> Vec<ImageView> {
+        self.placements
+            .iter()
+            .filter(|(_, _, p)| p.line + p.rows > top && p.line < top + rows)
+            .map(|(_, view, p)| ImageView {
+                id: *view,
+                row: isize::try_from(p.line).unwrap_or(isize::MAX)
+                    - isize::try_from(top).unwrap_or(isize::MAX),
+                col: p.col,
+                width: p.width,
+                height: p.height,
+                offset: p.offset,
+                z: p.z,
+                url: p.url.clone(),
+            })
+            .collect()
+    }
+
+    fn get(&self, key: u64) -> Option<&StoredImage> {
+        self.images.iter().find(|stored| stored.key == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32) -> Image {
+        Image::new(width, height, vec![255; (width * height * 4) as usize])
+    }
+
+    fn placement(line: usize, rows: usize) -> Placement {
+        Placement {
+            id: 0,
+            line,
+            col: 2,
+            rows,
+            cols: 1,
+            width: 10,
+            height: 40,
+            offset: (0, 0),
+            z: 0,
+            url: "data:".into(),
+        }
+    }
+
+    #[test]
+    fn test_data_url() {
+        let url = image(2, 2).to_data_url().unwrap();
+        assert!(url.starts_with("data:image/png;base64,iVBORw0KGgo"));
+    }
+
+    #[test]
+    fn test_decode() {
+        let url = image(3, 2).to_data_url().unwrap();
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(&url["data:image/png;base64,".len()..])
+            .unwrap();
+        assert_eq!(Image::decode(&png, 1 << 20), Ok(image(3, 2)));
+        assert_eq!(Image::decode(&png, 8), Err(DecodeError::TooLarge));
+        assert_eq!(Image::decode(b"GIF89a", 1 << 20), Err(DecodeError::Invalid));
+        assert_eq!(Image::decode(b"text", 1 << 20), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn test_placement_cells() {
+        let placement = Placement::new(5, 1, (17, 16), (8, 16), "data:".into());
+        assert_eq!((placement.rows, placement.cols), (1, 3));
+        assert!(placement.covers(5, 3));
+        assert!(!placement.covers(6, 1));
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut img = image(3, 2);
+        img.rgba[(3 + 2) * 4] = 7;
+        let cropped = img.crop(1, 1, 5, 5);
+        assert_eq!((cropped.width, cropped.height), (2, 1));
+        assert_eq!(cropped.rgba[4], 7);
+    }
+
+    #[test]
+    fn test_place_and_scroll() {
+        let mut store = ImageStore::default();
+        let key = store.add(image(10, 40), None, None).unwrap();
+        store.place(key, placement(3, 3));
+        let views = store.visible(0, 24);
+        assert_eq!(views.len(), 1);
+        assert_eq!((views[0].row, views[0].col), (3, 2));
+        // Partly scrolled off
+        assert_eq!(store.visible(4, 24)[0].row, -1);
+        store.prune(6);
+        assert!(store.visible(6, 24).is_empty());
+        assert_eq!(store.used(), 0);
+    }
+
+    #[test]
+    fn test_ids_and_delete() {
+        let mut store = ImageStore::default();
+        let key = store.add(image(1, 1), Some(5), Some(9)).unwrap();
+        assert_eq!(store.find(5), Some(key));
+        assert_eq!(store.find_number(9), Some(key));
+        assert_eq!(store.free_id(), 1);
+        store.place(
+            key,
+            Placement {
+                id: 1,
+                ..placement(0, 1)
+            },
+        );
+        store.place(
+            key,
+            Placement {
+                id: 1,
+                ..placement(1, 1)
+            },
+        );
+        store.place(key, placement(2, 1));
+        assert_eq!(store.visible(0, 24).len(), 2);
+        // Scrolled-off kitty images are kept for later placements
+        store.prune(3);
+        assert_eq!(store.find(5), Some(key));
+        store.place(key, placement(4, 1));
+        store.delete(false, |id, p| id == Some(5) && p.covers(4, 2));
+        assert!(store.visible(0, 24).is_empty());
+        assert_eq!(store.find(5), Some(key));
+        store.place(key, placement(4, 1));
+        store.delete(true, |_, _| true);
+        assert_eq!(store.find(5), None);
+        assert_eq!(store.used(), 0);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // Each image counts its 400 bytes of pixels and its data URL
+        let size = image(10, 10).to_data_url().unwrap().len() + 400;
+        let mut store = ImageStore {
+            limit: size * 5 / 2,
+            ..ImageStore::default()
+        };
+        // The third evicts the first
+        let keys: Vec<_> = (1..=3)
+            .map(|id| store.add(image(10, 10), Some(id), None).unwrap())
+            .collect();
+        assert_eq!(store.used(), 2 * size);
+        assert_eq!(store.image(keys[0]), None);
+        assert_eq!(store.find(3), Some(keys[2]));
+        assert_eq!(store.add(image(20, 20), None, None), None);
+        // Cropped placements count their own data URL, evicting older images
+        let crop = |len: usize| Placement {
+            url: "x".repeat(len).into(),
+            ..placement(0, 1)
+        };
+        assert!(store.place(keys[2], crop(size / 2 + 1)));
+        assert_eq!(store.image(keys[1]), None);
+        assert_eq!(store.used(), size + size / 2 + 1);
+        assert!(!store.place(keys[2], crop(size)));
+        assert_eq!(store.visible(0, 24).len(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+//! Handle for querying a running terminal from outside the component
+
+use dioxus::prelude::*;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::blocks::{CommandBlock, lines_text};
+use crate::emulator::TermState;
+use crate::term::{Grid, PromptMark};
+
+/// Handle to a running [`Terminal`](crate::Terminal), passed to `on_ready`
+#[derive(Clone)]
+pub struct TerminalHandle {
+    state: Arc<Mutex<TermState>>,
+    grid: Signal<Grid>,
+}
+
+impl std::fmt::Debug for TerminalHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TerminalHandle").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for TerminalHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state) && self.grid == other.grid
+    }
+}
+
+impl TerminalHandle {
+    pub(crate) const fn new(state: Arc<Mutex<TermState>>, grid: Signal<Grid>) -> Self {
+        Self { state, grid }
+    }
+
+    /// Current working directory of the shell, if known
+    ///
+    /// Uses the last OSC 7 report, falling back to the foreground process's
+    /// directory from `/proc`.
+    #[must_use]
+    pub fn cwd(&self) -> Option<PathBuf> {
+        let s = self.state.lock().ok()?;
+        if s.cwd_reported {
+            return s.cwd.clone();
+        }
+        s.pty
+            .as_ref()
+            .and_then(crate::pty::Pty::cwd)
+            .or_else(|| s.cwd.clone())
+    }
+
+    /// Command blocks from shell integration (OSC 133), oldest first
+    #[must_use]
+    pub fn command_blocks(&self) -> Vec<CommandBlock> {
+        self.state
+            .lock()
+            .map(|s| s.commands.blocks().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Output of the last finished command, as far as it is still on screen
+    #[must_use]
+    pub fn last_command_output(&self) -> Option<String> {
+        let s = self.state.lock().ok()?;
+        let block = s.commands.blocks().iter().rev().find(|b| b.finished)?;
+        let output = block.output.as_ref()?;
+        // Line numbers of the published grid, which lags behind during synchronized output
+        Some(lines_text(&self.grid.peek(), s.published_lines, output))
+    }
+
+    /// Rows where a prompt starts, top to bottom, for jumping between prompts
+    #[must_use]
+    pub fn prompt_rows(&self) -> Vec<usize> {
+        self.grid
+            .peek()
+            .rows_with_mark(PromptMark::PromptStart)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::emulator::process_byte;
+
+    /// Run `f` with a handle to a terminal that has processed `output`
+    fn with_handle(output: &[u8], f: impl FnOnce(&TerminalHandle)) {
+        let dom = VirtualDom::new(|| rsx! {});
+        dom.in_scope(ScopeId::ROOT, || {
+            let state = Arc::new(Mutex::new(TermState::new(None)));
+            let mut grid = Signal::new(Grid::new(4, 10));
+            {
+                let mut s = state.lock().unwrap();
+                let mut g = grid.write();
+                for &byte in output {
+                    process_byte(&mut s, &mut g, byte, 4, 10);
+                }
+                s.published_lines = s.scrolled_lines;
+            }
+            f(&TerminalHandle::new(state, grid));
+        });
+    }
+
+    #[test]
+    fn test_cwd_from_osc7() {
+        with_handle(b"", |handle| assert_eq!(handle.cwd(), None));
+        with_handle(b"\x1b]7;file:///tmp\x07", |handle| {
+            assert_eq!(handle.cwd(), Some(PathBuf::from("/tmp")));
+            assert_eq!(handle, &handle.clone());
+        });
+    }
+
+    #[test]
+    fn test_command_blocks() {
+        let output = b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07a\r\nb\r\n\x1b]133;D;0\x07\x1b]133;A\x07$ ";
+        with_handle(output, |handle| {
+            let blocks = handle.command_blocks();
+            assert_eq!(blocks.len(), 2);
+            assert_eq!(blocks[0].command, "ls");
+            assert_eq!(blocks[0].exit_code, Some(0));
+            assert_eq!(handle.last_command_output(), Some("a\nb".to_string()));
+            assert_eq!(handle.prompt_rows(), vec![0, 3]);
+            // Output scrolling a screen that isn't published yet doesn't shift the text
+            let mut s = handle.state.lock().unwrap();
+            let mut screen = handle.grid.peek().clone();
+            for &byte in b"\r\n" {
+                process_byte(&mut s, &mut screen, byte, 4, 10);
+            }
+            assert_eq!(s.scrolled_lines, 1);
+            drop(s);
+            assert_eq!(handle.last_command_output(), Some("a\nb".to_string()));
+        });
+    }
+}
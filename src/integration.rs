@@ -0,0 +1,327 @@
+//! Shell integration scripts injected at spawn time
+//!
+//! The scripts report prompts (OSC 133) and the working directory (OSC 7).
+//! They are written to a private directory and loaded through the shell's
+//! startup options, so the user's dotfiles are never modified.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const BASH_SCRIPT: &str = include_str!("../shell/dioxus-terminal.bash");
+const ZSH_SCRIPT: &str = include_str!("../shell/dioxus-terminal.zsh");
+const FISH_SCRIPT: &str = include_str!("../shell/dioxus-terminal.fish");
+const ZSH_ZSHENV: &str = include_str!("../shell/zsh/.zshenv");
+const ZSH_ZPROFILE: &str = include_str!("../shell/zsh/.zprofile");
+const ZSH_ZSHRC: &str = include_str!("../shell/zsh/.zshrc");
+
+/// Shells with bundled integration scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Detect the shell from a command name or path (e.g. `/bin/zsh`)
+    pub fn detect(command: &str) -> Option<Self> {
+        match Path::new(command).file_name()?.to_str()? {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+}
+
+/// Arguments and environment that load the integration script
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Injection {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+impl Injection {
+    /// Injection for `shell`, with the scripts written below `dir`
+    fn new(shell: Shell, dir: &Path) -> Self {
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        match shell {
+            Shell::Bash => Self {
+                args: vec![
+                    "--rcfile".to_string(),
+                    path("dioxus-terminal.bash"),
+                    "-i".to_string(),
+                ],
+                env: Vec::new(),
+            },
+            Shell::Zsh => {
+                // Our ZDOTDIR loads the user's startup files, found through this variable
+                let user_zdotdir = std::env::var("ZDOTDIR")
+                    .or_else(|_| std::env::var("HOME"))
+                    .unwrap_or_default();
+                Self {
+                    args: Vec::new(),
+                    env: vec![
+                        ("ZDOTDIR".to_string(), path("zsh")),
+                        ("DIOXUS_TERMINAL_USER_ZDOTDIR".to_string(), user_zdotdir),
+                    ],
+                }
+            }
+            Shell::Fish => Self {
+                args: vec![
+                    "--init-command".to_string(),
+                    format!(
+                        "source '{}'",
+                        path("dioxus-terminal.fish")
+                            .replace('\\', "\\\\")
+                            .replace('\'', "\\'")
+                    ),
+                ],
+                env: Vec::new(),
+            },
+        }
+    }
+}
+
+/// Write the integration scripts and return how to load them into `command`
+///
+/// Only interactive shells started without arguments are integrated; other
+/// commands get `None` and are spawned unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the scripts cannot be written.
+pub(crate) fn prepare(command: &str, args: &[&str]) -> io::Result<Option<Injection>> {
+    prepare_in(command, args, &script_dir())
+}
+
+/// Like [`prepare`], with the scripts written below `dir`
+fn prepare_in(command: &str, args: &[&str], dir: &Path) -> io::Result<Option<Injection>> {
+    let Some(shell) = Shell::detect(command) else {
+        return Ok(None);
+    };
+    if !args.is_empty() {
+        return Ok(None);
+    }
+    write_scripts(dir)?;
+    Ok(Some(Injection::new(shell, dir)))
+}
+
+/// Private per-user directory for the scripts
+///
+/// Without `XDG_RUNTIME_DIR` this is a predictable path in the shared temporary
+/// directory, so [`create_private_dir`] checks who owns it.
+fn script_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    let user = std::env::var("USER").unwrap_or_default();
+    base.join(format!(
+        "dioxus-terminal-{user}-{}",
+        env!("CARGO_PKG_VERSION")
+    ))
+}
+
+/// Write all scripts below `dir`
+fn write_scripts(dir: &Path) -> io::Result<()> {
+    create_private_dir(dir)?;
+    create_private_dir(&dir.join("zsh"))?;
+    for (name, contents) in [
+        ("dioxus-terminal.bash", BASH_SCRIPT),
+        ("dioxus-terminal.zsh", ZSH_SCRIPT),
+        ("dioxus-terminal.fish", FISH_SCRIPT),
+        ("zsh/.zshenv", ZSH_ZSHENV),
+        ("zsh/.zprofile", ZSH_ZPROFILE),
+        ("zsh/.zshrc", ZSH_ZSHRC),
+    ] {
+        write_file(&dir.join(name), contents)?;
+    }
+    Ok(())
+}
+
+/// Create a directory readable only by the current user, or check that an
+/// existing one is
+///
+/// A directory (or symlink) left at the path by another user is refused, as
+/// the shell would source whatever it contains.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    match builder.create(dir) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+        _ => {}
+    }
+    check_private_dir(dir)
+}
+
+/// Fail unless `dir` is a directory owned by the current user with mode 0700
+#[cfg(unix)]
+fn check_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::symlink_metadata(dir)?;
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    if metadata.is_dir() && metadata.uid() == uid && metadata.mode() & 0o777 == 0o700 {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is not a private directory", dir.display()),
+        ))
+    }
+}
+
+#[cfg(not(unix))]
+fn check_private_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Write a file through a new temporary file renamed over `path`
+///
+/// The temporary file is created exclusively and the rename replaces whatever
+/// is at `path`, so symlinks are never followed.
+fn write_file(path: &Path, contents: &str) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(
+        ".{name}.{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let result = options
+        .open(&temp)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .and_then(|()| std::fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_shell() {
+        assert_eq!(Shell::detect("/bin/bash"), Some(Shell::Bash));
+        assert_eq!(Shell::detect("zsh"), Some(Shell::Zsh));
+        assert_eq!(Shell::detect("/usr/local/bin/fish"), Some(Shell::Fish));
+        assert_eq!(Shell::detect("/bin/sh"), None);
+        assert_eq!(Shell::detect("htop"), None);
+    }
+
+    #[test]
+    fn test_injection() {
+        let dir = Path::new("/run/dt");
+        assert_eq!(
+            Injection::new(Shell::Bash, dir).args,
+            ["--rcfile", "/run/dt/dioxus-terminal.bash", "-i"]
+        );
+        let zsh = Injection::new(Shell::Zsh, dir);
+        assert!(zsh.args.is_empty());
+        assert_eq!(
+            zsh.env[0],
+            ("ZDOTDIR".to_string(), "/run/dt/zsh".to_string())
+        );
+        assert_eq!(
+            Injection::new(Shell::Fish, dir).args[1],
+            "source '/run/dt/dioxus-terminal.fish'"
+        );
+    }
+
+    /// Fresh directory for a test, removed when dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "dioxus-terminal-test-{name}-{}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_prepare_writes_scripts() {
+        let temp = TestDir::new("prepare");
+        let dir = temp.0.join("scripts");
+        let injection = prepare_in("bash", &[], &dir).unwrap().unwrap();
+        let script = PathBuf::from(&injection.args[1]);
+        assert_eq!(std::fs::read_to_string(&script).unwrap(), BASH_SCRIPT);
+        // Writing again replaces the scripts
+        assert!(prepare_in("zsh", &[], &dir).unwrap().is_some());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("zsh/.zshrc")).unwrap(),
+            ZSH_ZSHRC
+        );
+        // Commands with arguments are left alone
+        assert_eq!(prepare_in("bash", &["-c", "ls"], &dir).unwrap(), None);
+        assert_eq!(prepare_in("vim", &[], &dir).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_refuses_shared_dir_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let temp = TestDir::new("shared");
+        // A directory others can write to is refused
+        let dir = temp.0.join("scripts");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(prepare_in("bash", &[], &dir).is_err());
+        // So is a symlink in place of the directory
+        let link = temp.0.join("link");
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+        assert!(prepare_in("bash", &[], &link).is_err());
+        // A symlink in place of a script is replaced, not written through
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let victim = temp.0.join("victim");
+        std::fs::write(&victim, "keep").unwrap();
+        std::os::unix::fs::symlink(&victim, dir.join("dioxus-terminal.bash")).unwrap();
+        prepare_in("bash", &[], &dir).unwrap();
+        assert_eq!(std::fs::read_to_string(&victim).unwrap(), "keep");
+        let script = std::fs::symlink_metadata(dir.join("dioxus-terminal.bash")).unwrap();
+        assert!(script.is_file());
+    }
+
+    #[test]
+    #[ignore = "spawns zsh"]
+    fn test_zsh_follows_zdotdir_from_zshenv() {
+        let temp = TestDir::new("zdotdir");
+        let dir = temp.0.join("scripts");
+        prepare_in("zsh", &[], &dir).unwrap();
+        // The user's .zshenv moves their startup files to ~/.config/zsh
+        let home = temp.0.join("home");
+        let config = home.join(".config/zsh");
+        std::fs::create_dir_all(&config).unwrap();
+        std::fs::write(home.join(".zshenv"), "ZDOTDIR=$HOME/.config/zsh\n").unwrap();
+        std::fs::write(config.join(".zshrc"), "print -r -- user-zshrc\n").unwrap();
+        let output = std::process::Command::new("zsh")
+            .args(["-i", "-c", "print -r -- $ZDOTDIR"])
+            .env("HOME", &home)
+            .env("ZDOTDIR", dir.join("zsh"))
+            .env("DIOXUS_TERMINAL_USER_ZDOTDIR", &home)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("user-zshrc"), "{stdout}");
+        assert!(
+            stdout.contains(&config.to_string_lossy().into_owned()),
+            "{stdout}"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! iTerm2 inline images (OSC 1337 `File=`)
+//!
+//! A file is sent as `File=key=value;...:base64`, or in parts as
+//! `MultipartFile=key=value;...`, any number of `FilePart=base64` and a final
+//! `FileEnd`. Only inline files are shown; downloads are ignored.
+
+use base64::Engine;
+
+/// Requested width or height of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Dimension {
+    /// The image's own size
+    #[default]
+    Auto,
+    /// A number of cells
+    Cells(u32),
+    /// A number of pixels (`Npx`)
+    Pixels(u32),
+    /// A percentage of the terminal's width or height (`N%`)
+    Percent(u32),
+}
+
+impl Dimension {
+    fn parse(value: &str) -> Option<Self> {
+        if value == "auto" {
+            Some(Self::Auto)
+        } else if let Some(px) = value.strip_suffix("px") {
+            px.parse().ok().map(Self::Pixels)
+        } else if let Some(percent) = value.strip_suffix('%') {
+            percent.parse().ok().map(Self::Percent)
+        } else {
+            value.parse().ok().map(Self::Cells)
+        }
+    }
+
+    /// Size in pixels given the cell size and terminal size (`None` for auto)
+    fn to_pixels(self, cell: u32, total: u32) -> Option<u32> {
+        match self {
+            Self::Auto => None,
+            Self::Cells(cells) => Some(cells.saturating_mul(cell)),
+            Self::Pixels(px) => Some(px),
+            Self::Percent(percent) => Some(total.saturating_mul(percent.min(100)) / 100),
+        }
+    }
+}
+
+/// Arguments of a `File=` or `MultipartFile=` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileArgs {
+    pub width: Dimension,
+    pub height: Dimension,
+    /// Keep the aspect ratio when both dimensions are given (default)
+    pub preserve_aspect_ratio: bool,
+    /// Show the file inline rather than download it
+    pub inline: bool,
+    /// Leave the cursor where it is (`doNotMoveCursor=1`)
+    pub no_move: bool,
+}
+
+impl Default for FileArgs {
+    fn default() -> Self {
+        Self {
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            preserve_aspect_ratio: true,
+            inline: false,
+            no_move: false,
+        }
+    }
+}
+
+impl FileArgs {
+    fn parse(args: &str) -> Self {
+        let mut parsed = Self::default();
+        for (key, value) in args.split(';').filter_map(|arg| arg.split_once('=')) {
+            match key {
+                "width" => parsed.width = Dimension::parse(value).unwrap_or_default(),
+                "height" => parsed.height = Dimension::parse(value).unwrap_or_default(),
+                "preserveAspectRatio" => parsed.preserve_aspect_ratio = value != "0",
+                "inline" => parsed.inline = value == "1",
+                "doNotMoveCursor" => parsed.no_move = value == "1",
+                _ => {}
+            }
+        }
+        parsed
+    }
+
+    /// Displayed size in pixels of an `image` (width, height) sized image
+    ///
+    /// `cell` is the cell size and `screen` the terminal size, both in pixels.
+    pub fn display_size(
+        &self,
+        image: (u32, u32),
+        cell: (u32, u32),
+        screen: (u32, u32),
+    ) -> (u32, u32) {
+        let (image_width, image_height) = (image.0.max(1), image.1.max(1));
+        let scale = |value: u32, to: u32, from: u32| {
+            u32::try_from(u64::from(value) * u64::from(to) / u64::from(from)).unwrap_or(u32::MAX)
+        };
+        let width = self.width.to_pixels(cell.0, screen.0);
+        let height = self.height.to_pixels(cell.1, screen.1);
+        match (width, height) {
+            (None, None) => (image_width, image_height),
+            (Some(width), None) => (width, scale(image_height, width, image_width)),
+            (None, Some(height)) => (scale(image_width, height, image_height), height),
+            (Some(width), Some(height)) if self.preserve_aspect_ratio => {
+                // Fit inside the box
+                let fit_height = scale(image_height, width, image_width);
+                if fit_height <= height {
+                    (width, fit_height)
+                } else {
+                    (scale(image_width, height, image_height), height)
+                }
+            }
+            (Some(width), Some(height)) => (width, height),
+        }
+    }
+}
+
+/// A parsed OSC 1337 file command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FileCommand {
+    /// A whole file with its base64 contents
+    File(FileArgs, String),
+    /// Start of a file sent in parts
+    MultipartFile(FileArgs),
+    /// Base64 contents of the next part
+    FilePart(String),
+    /// End of a file sent in parts
+    FileEnd,
+}
+
+/// Parse OSC 1337 data; other iTerm2 commands give `None`
+pub(crate) fn parse_osc1337(data: &str) -> Option<FileCommand> {
+    if let Some(rest) = data.strip_prefix("File=") {
+        let (args, contents) = rest.split_once(':')?;
+        Some(FileCommand::File(
+            FileArgs::parse(args),
+            contents.to_string(),
+        ))
+    } else if let Some(args) = data.strip_prefix("MultipartFile=") {
+        Some(FileCommand::MultipartFile(FileArgs::parse(args)))
+    } else if let Some(contents) = data.strip_prefix("FilePart=") {
+        Some(FileCommand::FilePart(contents.to_string()))
+    } else {
+        (data == "FileEnd").then_some(FileCommand::FileEnd)
+    }
+}
+
+/// Decode base64 file contents, ignoring whitespace
+pub(crate) fn decode_contents(contents: &str) -> Option<Vec<u8>> {
+    let contents: Vec<u8> = contents
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(contents)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file() {
+        let Some(FileCommand::File(args, contents)) =
+            parse_osc1337("File=name=YS5wbmc=;width=10;height=50%;inline=1:AAAA")
+        else {
+            panic!("not a file");
+        };
+        assert_eq!(args.width, Dimension::Cells(10));
+        assert_eq!(args.height, Dimension::Percent(50));
+        assert!(args.inline && args.preserve_aspect_ratio);
+        assert_eq!(contents, "AAAA");
+        assert_eq!(parse_osc1337("SetMark"), None);
+        assert_eq!(parse_osc1337("FileEnd"), Some(FileCommand::FileEnd));
+        assert_eq!(
+            parse_osc1337("FilePart=QUJD"),
+            Some(FileCommand::FilePart("QUJD".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_display_size() {
+        let cell = (8, 16);
+        let screen = (640, 384);
+        let size = |args: &str| FileArgs::parse(args).display_size((200, 100), cell, screen);
+        assert_eq!(size(""), (200, 100));
+        assert_eq!(size("width=10"), (80, 40));
+        assert_eq!(size("height=50px"), (100, 50));
+        assert_eq!(size("width=100%"), (640, 320));
+        // Both given: fit inside, unless the aspect ratio may change
+        assert_eq!(size("width=10;height=1"), (32, 16));
+        assert_eq!(size("width=10;height=1;preserveAspectRatio=0"), (80, 16));
+    }
+
+    #[test]
+    fn test_decode_contents() {
+        assert_eq!(decode_contents("QU\nJD").as_deref(), Some(&b"ABC"[..]));
+        assert_eq!(decode_contents("!!"), None);
+    }
+}
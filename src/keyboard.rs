@@ -0,0 +1,609 @@
+//! Keyboard input encoding (legacy xterm, `modifyOtherKeys` and kitty keyboard protocol)
+
+use dioxus::prelude::*;
+use std::fmt::Write;
+
+/// Kitty flag: disambiguate escape codes
+const KITTY_DISAMBIGUATE: u16 = 0b1;
+/// Kitty flag: report key repeat and release events
+const KITTY_REPORT_EVENTS: u16 = 0b10;
+/// Kitty flag: report shifted keys as alternate key codes
+const KITTY_REPORT_ALTERNATES: u16 = 0b100;
+/// Kitty flag: report all keys (including text) as escape codes
+const KITTY_REPORT_ALL: u16 = 0b1000;
+/// Kitty flag: report associated text with escape codes
+const KITTY_REPORT_TEXT: u16 = 0b1_0000;
+/// All kitty flags this encoder understands
+const KITTY_ALL_FLAGS: u16 = 0b1_1111;
+
+/// Maximum depth of the kitty flag stack (oldest entries are evicted)
+const KITTY_STACK_LIMIT: usize = 16;
+
+/// Keyboard reporting modes negotiated by the running application
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct KeyboardModes {
+    kitty: u16,
+    kitty_stack: Vec<u16>,
+    modify_other_keys: u16,
+}
+
+impl KeyboardModes {
+    /// Currently active kitty progressive enhancement flags
+    pub const fn kitty_flags(&self) -> u16 {
+        self.kitty
+    }
+
+    /// Apply `CSI = flags ; mode u` (1 = replace, 2 = set bits, 3 = clear bits)
+    pub fn set_kitty(&mut self, flags: u16, mode: u16) {
+        let flags = flags & KITTY_ALL_FLAGS;
+        match mode {
+            1 => self.kitty = flags,
+            2 => self.kitty |= flags,
+            3 => self.kitty &= !flags,
+            _ => {}
+        }
+    }
+
+    /// Apply `CSI > flags u`: save the current flags and activate `flags`
+    pub fn push_kitty(&mut self, flags: u16) {
+        if self.kitty_stack.len() >= KITTY_STACK_LIMIT {
+            self.kitty_stack.remove(0);
+        }
+        self.kitty_stack.push(self.kitty);
+        self.kitty = flags & KITTY_ALL_FLAGS;
+    }
+
+    /// Apply `CSI < n u`: restore flags saved `n` pushes ago
+    pub fn pop_kitty(&mut self, n: usize) {
+        for _ in 0..n.max(1) {
+            if let Some(flags) = self.kitty_stack.pop() {
+                self.kitty = flags;
+            } else {
+                self.kitty = 0;
+                break;
+            }
+        }
+    }
+
+    /// Current xterm `modifyOtherKeys` level (0 = off)
+    pub const fn modify_other_keys(&self) -> u16 {
+        self.modify_other_keys
+    }
+
+    /// Apply `CSI > 4 ; level m` (levels above 2 are clamped)
+    pub fn set_modify_other_keys(&mut self, level: u16) {
+        self.modify_other_keys = level.min(2);
+    }
+}
+
+/// Kind of keyboard event being encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyEventKind {
+    Press,
+    Repeat,
+    Release,
+}
+
+/// Keyboard event data needed for encoding, decoupled from `KeyboardEvent`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct KeyInput {
+    pub key: Key,
+    pub code: Code,
+    pub modifiers: Modifiers,
+    pub kind: KeyEventKind,
+}
+
+impl KeyInput {
+    /// Capture a Dioxus keyboard event (`released` for `onkeyup`)
+    pub fn from_event(evt: &KeyboardEvent, released: bool) -> Self {
+        let kind = if released {
+            KeyEventKind::Release
+        } else if evt.is_auto_repeating() {
+            KeyEventKind::Repeat
+        } else {
+            KeyEventKind::Press
+        };
+        Self {
+            key: evt.key(),
+            code: evt.code(),
+            modifiers: evt.modifiers(),
+            kind,
+        }
+    }
+}
+
+/// Convert keyboard input to the bytes the application expects
+pub(crate) fn key_to_string(input: &KeyInput, modes: &KeyboardModes) -> String {
+    let flags = modes.kitty_flags();
+    if flags != 0 {
+        if let Some(seq) = encode_kitty(input, flags) {
+            return seq;
+        }
+    }
+    if input.kind == KeyEventKind::Release {
+        return String::new();
+    }
+    if let Some(seq) = encode_modify_other_keys(input, modes.modify_other_keys()) {
+        return seq;
+    }
+    encode_legacy(input)
+}
+
+/// xterm `modifyOtherKeys` encoding (`CSI 27 ; mods ; code ~`), or `None` for legacy
+fn encode_modify_other_keys(input: &KeyInput, level: u16) -> Option<String> {
+    if level == 0 {
+        return None;
+    }
+    let mods = modifier_bits(input.modifiers) & !(MOD_CAPS_LOCK | MOD_NUM_LOCK);
+    let code = match &input.key {
+        // Shifted text is already unambiguous
+        Key::Character(_) if mods & !MOD_SHIFT == 0 => return None,
+        Key::Character(c) => {
+            let ch = c.chars().next()?;
+            // Level 1 leaves keys with a well-known control encoding alone
+            if level == 1 && !(mods & MOD_CTRL != 0 && !ch.is_ascii_lowercase() && ch != ' ') {
+                return None;
+            }
+            u32::from(ch)
+        }
+        // Shift+Tab keeps its well-known CSI Z
+        Key::Tab if level == 2 && mods & !MOD_SHIFT != 0 => 9,
+        Key::Enter if level == 2 && mods != 0 => 13,
+        Key::Backspace if level == 2 && mods != 0 => 127,
+        Key::Escape if level == 2 && mods != 0 => 27,
+        _ => return None,
+    };
+    Some(format!("\x1b[27;{};{code}~", mods + 1))
+}
+
+/// Legacy (xterm-style) encoding
+fn encode_legacy(input: &KeyInput) -> String {
+    let mods = modifier_bits(input.modifiers);
+    // Kitty moved F3 to `CSI 13 ~`; legacy terminals send SS3 R
+    let functional = match input.key {
+        Key::F3 => Some((1, b'R')),
+        ref key => functional_key(key),
+    };
+    if let Some((number, action)) = functional {
+        return match (number, mods) {
+            // F1-F4 without modifiers use SS3
+            (1, 0) if matches!(action, b'P'..=b'S') => format!("\x1bO{}", action as char),
+            (1, 0) => format!("\x1b[{}", action as char),
+            (_, 0) => format!("\x1b[{number}{}", action as char),
+            _ => format!("\x1b[{number};{}{}", mods + 1, action as char),
+        };
+    }
+
+    match &input.key {
+        Key::Enter => "\r".to_string(),
+        Key::Backspace => "\x7f".to_string(),
+        Key::Tab if input.modifiers.shift() => "\x1b[Z".to_string(),
+        Key::Tab => "\t".to_string(),
+        Key::Escape => "\x1b".to_string(),
+        Key::Character(c) => {
+            // Handle Ctrl+key combinations
+            if input.modifiers.ctrl() && c.len() == 1 {
+                let ch = c.chars().next().unwrap();
+                if ch.is_ascii_lowercase() {
+                    // Ctrl+a = 0x01, Ctrl+b = 0x02, etc.
+                    let ctrl_char = (ch as u8 - b'a' + 1) as char;
+                    return ctrl_char.to_string();
+                }
+            }
+            c.clone()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Kitty protocol encoding, or `None` when the key keeps its legacy encoding
+fn encode_kitty(input: &KeyInput, flags: u16) -> Option<String> {
+    let report_all = flags & KITTY_REPORT_ALL != 0;
+    let disambiguate = report_all || flags & KITTY_DISAMBIGUATE != 0;
+    let report_events = flags & KITTY_REPORT_EVENTS != 0;
+    let release = input.kind == KeyEventKind::Release;
+    if release && !report_events {
+        return Some(String::new());
+    }
+
+    let mods = modifier_bits(input.modifiers);
+    // Modifiers other than Shift and the lock keys
+    let chorded = mods & !(MOD_SHIFT | MOD_CAPS_LOCK | MOD_NUM_LOCK) != 0;
+    let event = match input.kind {
+        KeyEventKind::Press => 1,
+        KeyEventKind::Repeat => 2,
+        KeyEventKind::Release => 3,
+    };
+
+    if let Some((number, action)) = functional_key(&input.key) {
+        if mods == 0 && event == 1 && !report_all {
+            return None;
+        }
+        return Some(kitty_sequence(number, None, mods, event, None, action));
+    }
+
+    let (number, shifted, text) = match &input.key {
+        Key::Enter | Key::Tab | Key::Backspace => {
+            // Kept legacy so a crashed program doesn't leave the shell unusable
+            if !report_all && (mods == 0 || (mods == MOD_SHIFT && input.key == Key::Tab)) {
+                return if release { Some(String::new()) } else { None };
+            }
+            let number = match input.key {
+                Key::Enter => 13,
+                Key::Tab => 9,
+                _ => 127,
+            };
+            (number, None, None)
+        }
+        Key::Escape if disambiguate => (27, None, None),
+        Key::Escape => return None,
+        Key::Character(c) => {
+            let ch = c.chars().next()?;
+            let escaped = report_all || release || (chorded && disambiguate);
+            if !escaped {
+                return None;
+            }
+            let base = base_key(input.code, ch);
+            let shifted =
+                (flags & KITTY_REPORT_ALTERNATES != 0 && input.modifiers.shift() && ch != base)
+                    .then_some(u32::from(ch));
+            let text = (flags & KITTY_REPORT_TEXT != 0 && report_all && !release && !chorded)
+                .then(|| c.clone());
+            (u32::from(base), shifted, text)
+        }
+        key => match modifier_key_code(key, input.code) {
+            Some(number) if report_all => (number, None, None),
+            _ => return Some(String::new()),
+        },
+    };
+
+    Some(kitty_sequence(number, shifted, mods, event, text, b'u'))
+}
+
+/// Build `CSI number[:shifted] ; mods[:event] [; text] action`
+fn kitty_sequence(
+    number: u32,
+    shifted: Option<u32>,
+    mods: u16,
+    event: u8,
+    text: Option<String>,
+    action: u8,
+) -> String {
+    let mut seq = format!("\x1b[{number}");
+    if let Some(shifted) = shifted {
+        let _ = write!(seq, ":{shifted}");
+    }
+    if mods != 0 || event != 1 || text.is_some() {
+        let _ = write!(seq, ";{}", mods + 1);
+        if event != 1 {
+            let _ = write!(seq, ":{event}");
+        }
+    }
+    if let Some(text) = text {
+        let codepoints: Vec<String> = text.chars().map(|c| u32::from(c).to_string()).collect();
+        let _ = write!(seq, ";{}", codepoints.join(":"));
+    }
+    seq.push(action as char);
+    seq
+}
+
+const MOD_SHIFT: u16 = 1;
+const MOD_ALT: u16 = 2;
+const MOD_CTRL: u16 = 4;
+const MOD_SUPER: u16 = 8;
+const MOD_HYPER: u16 = 16;
+const MOD_CAPS_LOCK: u16 = 64;
+const MOD_NUM_LOCK: u16 = 128;
+
+/// Encode modifiers as the xterm/kitty bit set (parameter is this plus one)
+fn modifier_bits(modifiers: Modifiers) -> u16 {
+    let mut bits = 0;
+    if modifiers.contains(Modifiers::SHIFT) {
+        bits |= MOD_SHIFT;
+    }
+    if modifiers.contains(Modifiers::ALT) {
+        bits |= MOD_ALT;
+    }
+    if modifiers.contains(Modifiers::CONTROL) {
+        bits |= MOD_CTRL;
+    }
+    if modifiers.intersects(Modifiers::META | Modifiers::SUPER) {
+        bits |= MOD_SUPER;
+    }
+    if modifiers.contains(Modifiers::HYPER) {
+        bits |= MOD_HYPER;
+    }
+    if modifiers.contains(Modifiers::CAPS_LOCK) {
+        bits |= MOD_CAPS_LOCK;
+    }
+    if modifiers.contains(Modifiers::NUM_LOCK) {
+        bits |= MOD_NUM_LOCK;
+    }
+    bits
+}
+
+/// Number and final byte for cursor, editing and function keys
+fn functional_key(key: &Key) -> Option<(u32, u8)> {
+    Some(match key {
+        Key::ArrowUp => (1, b'A'),
+        Key::ArrowDown => (1, b'B'),
+        Key::ArrowRight => (1, b'C'),
+        Key::ArrowLeft => (1, b'D'),
+        Key::Home => (1, b'H'),
+        Key::End => (1, b'F'),
+        Key::F1 => (1, b'P'),
+        Key::F2 => (1, b'Q'),
+        Key::F3 => (13, b'~'),
+        Key::F4 => (1, b'S'),
+        Key::Insert => (2, b'~'),
+        Key::Delete => (3, b'~'),
+        Key::PageUp => (5, b'~'),
+        Key::PageDown => (6, b'~'),
+        Key::F5 => (15, b'~'),
+        Key::F6 => (17, b'~'),
+        Key::F7 => (18, b'~'),
+        Key::F8 => (19, b'~'),
+        Key::F9 => (20, b'~'),
+        Key::F10 => (21, b'~'),
+        Key::F11 => (23, b'~'),
+        Key::F12 => (24, b'~'),
+        _ => return None,
+    })
+}
+
+/// Kitty key codes for modifier keys (only reported with "report all keys")
+fn modifier_key_code(key: &Key, code: Code) -> Option<u32> {
+    Some(match (key, code) {
+        (Key::CapsLock, _) => 57358,
+        (Key::NumLock, _) => 57360,
+        (Key::Shift, Code::ShiftRight) => 57447,
+        (Key::Shift, _) => 57441,
+        (Key::Control, Code::ControlRight) => 57448,
+        (Key::Control, _) => 57442,
+        (Key::Alt, Code::AltRight) => 57449,
+        (Key::Alt, _) => 57443,
+        (Key::Meta | Key::Super, Code::MetaRight) => 57450,
+        (Key::Meta | Key::Super, _) => 57444,
+        (Key::Hyper, _) => 57445,
+        _ => return None,
+    })
+}
+
+/// Unshifted key for a physical key code (US layout), falling back to the character
+pub(crate) fn base_key(code: Code, ch: char) -> char {
+    let name = code.to_string();
+    let physical = name
+        .strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .filter(|rest| rest.len() == 1)
+        .and_then(|rest| rest.chars().next());
+    if let Some(c) = physical {
+        return c.to_ascii_lowercase();
+    }
+    match code {
+        Code::Minus => '-',
+        Code::Equal => '=',
+        Code::BracketLeft => '[',
+        Code::BracketRight => ']',
+        Code::Backslash => '\\',
+        Code::Semicolon => ';',
+        Code::Quote => '\'',
+        Code::Backquote => '`',
+        Code::Comma => ',',
+        Code::Period => '.',
+        Code::Slash => '/',
+        Code::Space => ' ',
+        _ => ch.to_lowercase().next().unwrap_or(ch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(key: Key, code: Code, modifiers: Modifiers) -> KeyInput {
+        KeyInput {
+            key,
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+        }
+    }
+
+    fn chr(c: &str, code: Code, modifiers: Modifiers) -> KeyInput {
+        input(Key::Character(c.to_string()), code, modifiers)
+    }
+
+    fn kitty(flags: u16) -> KeyboardModes {
+        let mut modes = KeyboardModes::default();
+        modes.push_kitty(flags);
+        modes
+    }
+
+    #[test]
+    fn test_legacy_keys() {
+        let modes = KeyboardModes::default();
+        let none = Modifiers::empty();
+        assert_eq!(
+            key_to_string(&input(Key::Enter, Code::Enter, none), &modes),
+            "\r"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::ArrowUp, Code::ArrowUp, none), &modes),
+            "\x1b[A"
+        );
+        assert_eq!(
+            key_to_string(
+                &input(Key::ArrowUp, Code::ArrowUp, Modifiers::CONTROL),
+                &modes
+            ),
+            "\x1b[1;5A"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::F1, Code::F1, none), &modes),
+            "\x1bOP"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::F5, Code::F5, none), &modes),
+            "\x1b[15~"
+        );
+        assert_eq!(
+            key_to_string(&chr("c", Code::KeyC, Modifiers::CONTROL), &modes),
+            "\x03"
+        );
+    }
+
+    #[test]
+    fn test_kitty_stack() {
+        let mut modes = KeyboardModes::default();
+        modes.push_kitty(1);
+        modes.push_kitty(0xff);
+        assert_eq!(modes.kitty_flags(), KITTY_ALL_FLAGS);
+        modes.pop_kitty(1);
+        assert_eq!(modes.kitty_flags(), 1);
+        modes.pop_kitty(3);
+        assert_eq!(modes.kitty_flags(), 0);
+    }
+
+    #[test]
+    fn test_kitty_stack_limit() {
+        let mut modes = KeyboardModes::default();
+        for flags in 1..=20 {
+            modes.push_kitty(flags);
+        }
+        assert_eq!(modes.kitty_stack.len(), KITTY_STACK_LIMIT);
+    }
+
+    #[test]
+    fn test_kitty_disambiguate() {
+        let modes = kitty(KITTY_DISAMBIGUATE);
+        let none = Modifiers::empty();
+        // Ctrl+I and Tab are distinguishable
+        assert_eq!(
+            key_to_string(&chr("i", Code::KeyI, Modifiers::CONTROL), &modes),
+            "\x1b[105;5u"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::Tab, Code::Tab, none), &modes),
+            "\t"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::Escape, Code::Escape, none), &modes),
+            "\x1b[27u"
+        );
+        // Plain and shifted text is still sent as text
+        assert_eq!(key_to_string(&chr("a", Code::KeyA, none), &modes), "a");
+        assert_eq!(
+            key_to_string(&chr("A", Code::KeyA, Modifiers::SHIFT), &modes),
+            "A"
+        );
+        assert_eq!(
+            key_to_string(&chr("a", Code::KeyA, Modifiers::ALT), &modes),
+            "\x1b[97;3u"
+        );
+    }
+
+    #[test]
+    fn test_kitty_release_events() {
+        let none = Modifiers::empty();
+        let mut release = chr("a", Code::KeyA, none);
+        release.kind = KeyEventKind::Release;
+        assert_eq!(key_to_string(&release, &kitty(KITTY_DISAMBIGUATE)), "");
+        assert_eq!(
+            key_to_string(&release, &kitty(KITTY_DISAMBIGUATE | KITTY_REPORT_EVENTS)),
+            "\x1b[97;1:3u"
+        );
+        let mut up = input(Key::ArrowUp, Code::ArrowUp, none);
+        up.kind = KeyEventKind::Repeat;
+        assert_eq!(
+            key_to_string(&up, &kitty(KITTY_DISAMBIGUATE | KITTY_REPORT_EVENTS)),
+            "\x1b[1;1:2A"
+        );
+    }
+
+    #[test]
+    fn test_kitty_report_all_with_alternates_and_text() {
+        let modes = kitty(
+            KITTY_DISAMBIGUATE | KITTY_REPORT_ALL | KITTY_REPORT_ALTERNATES | KITTY_REPORT_TEXT,
+        );
+        assert_eq!(
+            key_to_string(&chr("A", Code::KeyA, Modifiers::SHIFT), &modes),
+            "\x1b[97:65;2;65u"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::Enter, Code::Enter, Modifiers::empty()), &modes),
+            "\x1b[13u"
+        );
+        assert_eq!(
+            key_to_string(
+                &input(Key::Shift, Code::ShiftLeft, Modifiers::SHIFT),
+                &modes
+            ),
+            "\x1b[57441;2u"
+        );
+    }
+
+    #[test]
+    fn test_modify_other_keys_level_2() {
+        let mut modes = KeyboardModes::default();
+        modes.set_modify_other_keys(2);
+        let ctrl_shift = Modifiers::CONTROL | Modifiers::SHIFT;
+        assert_eq!(
+            key_to_string(&chr("A", Code::KeyA, ctrl_shift), &modes),
+            "\x1b[27;6;65~"
+        );
+        assert_eq!(
+            key_to_string(&chr("a", Code::KeyA, Modifiers::CONTROL), &modes),
+            "\x1b[27;5;97~"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::Enter, Code::Enter, Modifiers::SHIFT), &modes),
+            "\x1b[27;2;13~"
+        );
+        // Unmodified and shifted text, and Shift+Tab, are unchanged
+        assert_eq!(
+            key_to_string(&chr("A", Code::KeyA, Modifiers::SHIFT), &modes),
+            "A"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::Tab, Code::Tab, Modifiers::SHIFT), &modes),
+            "\x1b[Z"
+        );
+    }
+
+    #[test]
+    fn test_modify_other_keys_level_1() {
+        let mut modes = KeyboardModes::default();
+        modes.set_modify_other_keys(1);
+        assert_eq!(
+            key_to_string(&chr("a", Code::KeyA, Modifiers::CONTROL), &modes),
+            "\x01"
+        );
+        assert_eq!(
+            key_to_string(&chr(";", Code::Semicolon, Modifiers::CONTROL), &modes),
+            "\x1b[27;5;59~"
+        );
+        assert_eq!(
+            key_to_string(&input(Key::Enter, Code::Enter, Modifiers::CONTROL), &modes),
+            "\r"
+        );
+    }
+
+    #[test]
+    fn test_kitty_takes_precedence_over_modify_other_keys() {
+        let mut modes = kitty(KITTY_DISAMBIGUATE);
+        modes.set_modify_other_keys(2);
+        assert_eq!(
+            key_to_string(&chr("a", Code::KeyA, Modifiers::CONTROL), &modes),
+            "\x1b[97;5u"
+        );
+    }
+
+    #[test]
+    fn test_base_key() {
+        assert_eq!(base_key(Code::Digit1, '!'), '1');
+        assert_eq!(base_key(Code::KeyQ, 'Q'), 'q');
+        assert_eq!(base_key(Code::Slash, '?'), '/');
+        assert_eq!(base_key(Code::Unidentified, 'É'), 'é');
+    }
+}
@@ -0,0 +1,385 @@
+//! Kitty graphics protocol (APC `_G`)
+//!
+//! Commands are `key=value` pairs, optionally followed by `;` and a base64
+//! payload. Large payloads arrive in chunks (`m=1`) that are collected before
+//! decoding. Only direct transmission is supported; images are never read
+//! from files or shared memory.
+
+use base64::Engine;
+use std::io::Read;
+
+use crate::graphics::{DecodeError, Image};
+
+/// What a graphics command asks for (`a=`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// `t` - store an image
+    Transmit,
+    /// `T` - store an image and display it
+    TransmitDisplay,
+    /// `p` - display a stored image
+    Display,
+    /// `d` - delete placements or images
+    Delete,
+    /// `q` - check whether an image would be accepted, without storing it
+    Query,
+}
+
+/// A parsed graphics command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GraphicsCommand {
+    pub action: Action,
+    /// Pixel format: 24 (RGB), 32 (RGBA) or 100 (PNG)
+    pub format: u32,
+    /// Transmission medium (`d` for direct)
+    pub medium: u8,
+    /// Payload is zlib compressed (`o=z`)
+    pub compressed: bool,
+    /// Size of raw pixel data (`s`, `v`)
+    pub width: u32,
+    pub height: u32,
+    /// Image id (`i`), image number (`I`) and placement id (`p`)
+    pub id: Option<u32>,
+    pub number: Option<u32>,
+    pub placement: u32,
+    /// More chunks follow (`m=1`)
+    pub more: bool,
+    /// 1 suppresses OK responses, 2 suppresses errors too
+    pub quiet: u32,
+    /// Source rectangle in pixels (`x`, `y`, `w`, `h`; 0 means the whole image)
+    pub source: (u32, u32, u32, u32),
+    /// Offset within the first cell in pixels (`X`, `Y`)
+    pub offset: (u32, u32),
+    /// Displayed size in cells (`c`, `r`; 0 means from the image size)
+    pub cols: u32,
+    pub rows: u32,
+    /// Leave the cursor where it is (`C=1`)
+    pub no_move: bool,
+    /// Stacking order (`z`)
+    pub z: i32,
+    /// What to delete (`d`)
+    pub delete: u8,
+    /// Base64 payload
+    pub payload: Vec<u8>,
+}
+
+impl Default for GraphicsCommand {
+    fn default() -> Self {
+        Self {
+            action: Action::Transmit,
+            format: 32,
+            medium: b'd',
+            compressed: false,
+            width: 0,
+            height: 0,
+            id: None,
+            number: None,
+            placement: 0,
+            more: false,
+            quiet: 0,
+            source: (0, 0, 0, 0),
+            offset: (0, 0),
+            cols: 0,
+            rows: 0,
+            no_move: false,
+            z: 0,
+            delete: b'a',
+            payload: Vec::new(),
+        }
+    }
+}
+
+impl GraphicsCommand {
+    /// Parse the APC data after `G`
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (control, payload) = match data.iter().position(|&b| b == b';') {
+            Some(i) => (&data[..i], &data[i + 1..]),
+            None => (data, &[][..]),
+        };
+        let mut command = Self {
+            payload: payload.to_vec(),
+            ..Self::default()
+        };
+        for pair in control.split(|&b| b == b',').filter(|p| !p.is_empty()) {
+            let [key, b'=', value @ ..] = pair else {
+                return None;
+            };
+            let number = || std::str::from_utf8(value).ok()?.parse::<u32>().ok();
+            let byte = value.first().copied();
+            match key {
+                b'a' => {
+                    command.action = match byte? {
+                        b't' => Action::Transmit,
+                        b'T' => Action::TransmitDisplay,
+                        b'p' => Action::Display,
+                        b'd' => Action::Delete,
+                        b'q' => Action::Query,
+                        _ => return None,
+                    }
+                }
+                b'f' => command.format = number()?,
+                b't' => command.medium = byte?,
+                b'o' => command.compressed = byte == Some(b'z'),
+                b's' => command.width = number()?,
+                b'v' => command.height = number()?,
+                b'i' => command.id = Some(number()?).filter(|&id| id != 0),
+                b'I' => command.number = Some(number()?).filter(|&n| n != 0),
+                b'p' => command.placement = number()?,
+                b'm' => command.more = byte == Some(b'1'),
+                b'q' => command.quiet = number()?,
+                b'x' => command.source.0 = number()?,
+                b'y' => command.source.1 = number()?,
+                b'w' => command.source.2 = number()?,
+                b'h' => command.source.3 = number()?,
+                b'X' => command.offset.0 = number()?,
+                b'Y' => command.offset.1 = number()?,
+                b'c' => command.cols = number()?,
+                b'r' => command.rows = number()?,
+                b'C' => command.no_move = byte == Some(b'1'),
+                b'z' => command.z = std::str::from_utf8(value).ok()?.parse().ok()?,
+                b'd' => command.delete = byte?,
+                // Keys for features we don't support are ignored
+                _ => {}
+            }
+        }
+        Some(command)
+    }
+
+    /// Response to send for `result`, unless suppressed by `q` or missing ids
+    ///
+    /// `id` is the image id, which may have been assigned for an image number.
+    pub fn response(&self, id: Option<u32>, result: Result<(), &str>) -> Option<String> {
+        let quiet = match result {
+            Ok(()) => self.quiet >= 1,
+            Err(_) => self.quiet >= 2,
+        };
+        if quiet || (self.id.is_none() && self.number.is_none()) {
+            return None;
+        }
+        let mut keys = Vec::new();
+        if let Some(id) = id.or(self.id) {
+            keys.push(format!("i={id}"));
+        }
+        if let Some(number) = self.number {
+            keys.push(format!("I={number}"));
+        }
+        if self.placement != 0 {
+            keys.push(format!("p={}", self.placement));
+        }
+        Some(format!(
+            "\x1b_G{};{}\x1b\\",
+            keys.join(","),
+            result.err().unwrap_or("OK")
+        ))
+    }
+}
+
+/// A chunked transmission being collected
+#[derive(Debug)]
+pub(crate) struct Transfer {
+    command: GraphicsCommand,
+    overflow: bool,
+}
+
+impl Transfer {
+    pub fn new(command: GraphicsCommand) -> Self {
+        Self {
+            command,
+            overflow: false,
+        }
+    }
+
+    /// Add a chunk's payload, up to `capacity` bytes of base64 in total
+    pub fn push(&mut self, payload: &[u8], capacity: usize) {
+        if self.command.payload.len() + payload.len() > capacity {
+            self.overflow = true;
+        } else if !self.overflow {
+            self.command.payload.extend_from_slice(payload);
+        }
+    }
+
+    /// The first chunk's command with the whole payload, or an error if it was too large
+    pub fn finish(self) -> Result<GraphicsCommand, GraphicsCommand> {
+        if self.overflow {
+            Err(self.command)
+        } else {
+            Ok(self.command)
+        }
+    }
+}
+
+/// Decode the image in `command`'s payload, within `max_bytes` of RGBA
+///
+/// Errors are protocol error strings (`CODE:message`).
+pub(crate) fn decode_image(command: &GraphicsCommand, max_bytes: usize) -> Result<Image, String> {
+    if command.medium != b'd' {
+        return Err("EINVAL:only direct transmission is supported".to_string());
+    }
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(
+            command
+                .payload
+                .iter()
+                .filter(|b| !b.is_ascii_whitespace())
+                .copied()
+                .collect::<Vec<_>>(),
+        )
+        .map_err(|_| "EINVAL:invalid base64 payload".to_string())?;
+    let data = if command.compressed {
+        let mut inflated = Vec::new();
+        flate2::read::ZlibDecoder::new(data.as_slice())
+            .take(max_bytes as u64 + 1)
+            .read_to_end(&mut inflated)
+            .map_err(|_| "EINVAL:invalid zlib data".to_string())?;
+        inflated
+    } else {
+        data
+    };
+    if data.is_empty() {
+        return Err("ENODATA:no image data".to_string());
+    }
+    match command.format {
+        100 => decode_png(&data, max_bytes),
+        24 | 32 => {
+            let channels = if command.format == 24 { 3 } else { 4 };
+            let (width, height) = (command.width, command.height);
+            let pixels = width as usize * height as usize;
+            if pixels == 0 {
+                return Err("EINVAL:missing image size".to_string());
+            }
+            if pixels.saturating_mul(4) > max_bytes {
+                return Err("EFBIG:image too large".to_string());
+            }
+            if data.len() < pixels * channels {
+                return Err("ENODATA:insufficient image data".to_string());
+            }
+            let rgba = if channels == 4 {
+                data[..pixels * 4].to_vec()
+            } else {
+                data[..pixels * 3]
+                    .chunks_exact(3)
+                    .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                    .collect()
+            };
+            Ok(Image::new(width, height, rgba))
+        }
+        format => Err(format!("EINVAL:unsupported format {format}")),
+    }
+}
+
+/// Decode PNG data to RGBA
+fn decode_png(data: &[u8], max_bytes: usize) -> Result<Image, String> {
+    if !data.starts_with(b"\x89PNG") {
+        return Err("EBADPNG:invalid PNG data".to_string());
+    }
+    Image::decode(data, max_bytes).map_err(|e| match e {
+        DecodeError::TooLarge => "EFBIG:image too large".to_string(),
+        DecodeError::Invalid => "EBADPNG:invalid PNG data".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let command = GraphicsCommand::parse(b"a=T,f=24,s=2,v=1,i=7,c=4,z=-1,C=1;AAAA").unwrap();
+        assert_eq!(command.action, Action::TransmitDisplay);
+        assert_eq!((command.format, command.width, command.height), (24, 2, 1));
+        assert_eq!(command.id, Some(7));
+        assert_eq!((command.cols, command.z, command.no_move), (4, -1, true));
+        assert_eq!(command.payload, b"AAAA");
+        // Defaults: transmit RGBA directly
+        let command = GraphicsCommand::parse(b"").unwrap();
+        assert_eq!((command.action, command.format), (Action::Transmit, 32));
+        assert_eq!(GraphicsCommand::parse(b"a=?"), None);
+    }
+
+    #[test]
+    fn test_response() {
+        let command = GraphicsCommand::parse(b"i=3,p=2").unwrap();
+        assert_eq!(
+            command.response(None, Ok(())).as_deref(),
+            Some("\x1b_Gi=3,p=2;OK\x1b\\")
+        );
+        assert_eq!(
+            command
+                .response(None, Err("ENOENT:no such image"))
+                .as_deref(),
+            Some("\x1b_Gi=3,p=2;ENOENT:no such image\x1b\\")
+        );
+        // No id, no response; q=1 silences OK only
+        assert_eq!(
+            GraphicsCommand::parse(b"").unwrap().response(None, Ok(())),
+            None
+        );
+        let quiet = GraphicsCommand::parse(b"i=1,q=1").unwrap();
+        assert_eq!(quiet.response(None, Ok(())), None);
+        assert!(quiet.response(None, Err("EINVAL:x")).is_some());
+        // An assigned id is reported alongside the number
+        let numbered = GraphicsCommand::parse(b"I=9").unwrap();
+        assert_eq!(
+            numbered.response(Some(4), Ok(())).as_deref(),
+            Some("\x1b_Gi=4,I=9;OK\x1b\\")
+        );
+    }
+
+    #[test]
+    fn test_decode_raw() {
+        let mut command = GraphicsCommand::parse(b"f=24,s=2,v=1").unwrap();
+        command.payload = encode(&[1, 2, 3, 4, 5, 6]).into_bytes();
+        let image = decode_image(&command, 1 << 20).unwrap();
+        assert_eq!(image.rgba, [1, 2, 3, 255, 4, 5, 6, 255]);
+        assert!(decode_image(&command, 4).unwrap_err().starts_with("EFBIG"));
+        command.height = 2;
+        assert!(
+            decode_image(&command, 1 << 20)
+                .unwrap_err()
+                .starts_with("ENODATA")
+        );
+        command.medium = b'f';
+        assert!(
+            decode_image(&command, 1 << 20)
+                .unwrap_err()
+                .starts_with("EINVAL")
+        );
+    }
+
+    #[test]
+    fn test_decode_png_and_zlib() {
+        let png = Image::new(1, 1, vec![9, 8, 7, 255]).to_data_url().unwrap();
+        let mut command = GraphicsCommand::parse(b"f=100").unwrap();
+        command.payload = png.as_bytes()["data:image/png;base64,".len()..].to_vec();
+        assert_eq!(
+            decode_image(&command, 1 << 20).unwrap().rgba,
+            [9, 8, 7, 255]
+        );
+        command.payload = encode(b"not a png").into_bytes();
+        assert!(
+            decode_image(&command, 1 << 20)
+                .unwrap_err()
+                .starts_with("EBADPNG")
+        );
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut zlib, &[1, 2, 3, 4]).unwrap();
+        let mut command = GraphicsCommand::parse(b"f=32,s=1,v=1,o=z").unwrap();
+        command.payload = encode(&zlib.finish().unwrap()).into_bytes();
+        assert_eq!(decode_image(&command, 1 << 20).unwrap().rgba, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut transfer = Transfer::new(GraphicsCommand::parse(b"i=1,m=1;AA").unwrap());
+        transfer.push(b"BB", 10);
+        assert_eq!(transfer.finish().unwrap().payload, b"AABB");
+        let mut transfer = Transfer::new(GraphicsCommand::parse(b"i=1,m=1;AA").unwrap());
+        transfer.push(b"BBBB", 4);
+        assert!(transfer.finish().is_err());
+    }
+}
@@ -9,7 +9,24 @@
 //!
 //! - Terminal emulation (VT100/xterm compatible)
 //! - ANSI color support (16 and 256 colors)
-//! - Keyboard input
+//! - DEC special graphics (line drawing) and G0-G3 character sets
+//! - Tab stops (HTS, TBC, CHT, CBT)
+//! - Terminal modes: autowrap with xterm-style pending wrap, insert, origin (with CUP and DECSTBM scroll margins), reverse video and newline (LNM)
+//! - Cursor save/restore (DECSC/DECRC, `CSI s`/`CSI u`), full reset (RIS) and soft reset (DECSTR)
+//! - Synchronized output (mode 2026): frames are shown once complete, with a timeout
+//! - Keyboard input (legacy xterm, `modifyOtherKeys` and kitty keyboard protocol)
+//! - Mouse reporting (X10, normal, button and any-event tracking)
+//! - Hyperlinks (OSC 8, Ctrl+click to open)
+//! - URL and `path:line:col` detection in plain output
+//! - Clipboard access over OSC 52 with a permission policy
+//! - Color queries and runtime palette changes (OSC 4/10/11/12)
+//! - Working directory tracking (OSC 7, `/proc` fallback)
+//! - Shell integration marks and command blocks (OSC 133)
+//! - Bundled bash, zsh and fish integration scripts, injected at spawn
+//! - Desktop notifications (OSC 9 / OSC 777, long-running commands)
+//! - Bell callback with an optional visual bell, rate limited
+//! - Double-width and double-height lines (DECDWL/DECDHL) and the DECALN alignment test
+//! - Inline images (Sixel, kitty graphics protocol, iTerm2 `OSC 1337`)
 //! - Customizable themes
 //!
 //! ## Example
@@ -43,15 +60,40 @@
 //! }
 //! ```
 
+mod blocks;
+mod charset;
+mod clipboard;
+mod emulator;
 mod error;
+mod graphics;
+mod handle;
+mod integration;
+mod iterm2;
+mod keyboard;
+mod kitty;
+mod links;
+mod modes;
+mod mouse;
+mod notification;
 mod pty;
+mod sixel;
 mod term;
 mod theme;
 mod widget;
 
+pub use blocks::CommandBlock;
+pub use clipboard::{ClipboardPolicy, ClipboardRequest, DEFAULT_CLIPBOARD_LIMIT};
 pub use error::Error;
+pub use graphics::DEFAULT_IMAGE_MEMORY_LIMIT;
+pub use handle::TerminalHandle;
+pub use links::{
+    DEFAULT_PATH_PATTERN, DEFAULT_URL_PATTERN, DetectedLink, LinkDetector, LinkTarget,
+};
+pub use notification::Notification;
 pub use pty::Pty;
-pub use term::{Cell, Color, Grid, Style};
+pub use term::{
+    Cell, Color, CursorShape, CursorStyle, Grid, Hyperlink, LineSize, PromptMark, Style,
+};
 pub use theme::Theme;
 pub use widget::{DEFAULT_FONT_FAMILY, Terminal, TerminalProps};
 
@@ -0,0 +1,302 @@
+//! Detection of URLs and `path:line:col` references in plain output
+
+use regex::Regex;
+
+use crate::error::Error;
+use crate::term::Grid;
+
+/// Default pattern for URLs
+pub const DEFAULT_URL_PATTERN: &str = r#"(?:https?|ftp|file)://[^\s<>"'`]+"#;
+
+/// Default pattern for file references such as `src/main.rs:10:5`
+///
+/// Path patterns must define a `path` group and may define `line` and `col` groups.
+pub const DEFAULT_PATH_PATTERN: &str =
+    r"(?P<path>(?:~|\.{1,2})?/?(?:[\w.-]+/)*[\w-][\w.-]*\.\w+):(?P<line>\d+)(?::(?P<col>\d+))?";
+
+/// What a detected link points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// A URL
+    Url(String),
+    /// A file reference with an optional position (1-based, as printed)
+    Path {
+        path: String,
+        line: Option<u32>,
+        col: Option<u32>,
+    },
+}
+
+/// A link found in the grid, spanning cells from `start` to `end` (inclusive)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedLink {
+    /// Link target
+    pub target: LinkTarget,
+    /// First cell as `(row, col)`
+    pub start: (usize, usize),
+    /// Last cell as `(row, col)`
+    pub end: (usize, usize),
+}
+
+impl DetectedLink {
+    /// Check whether the link covers a cell
+    #[must_use]
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        self.start <= (row, col) && (row, col) <= self.end
+    }
+}
+
+/// Regex-based detector for links in output that lacks OSC 8 markup
+#[derive(Debug, Clone)]
+pub struct LinkDetector {
+    url: Regex,
+    path: Regex,
+}
+
+impl Default for LinkDetector {
+    fn default() -> Self {
+        Self {
+            url: Regex::new(DEFAULT_URL_PATTERN).expect("default URL pattern is valid"),
+            path: Regex::new(DEFAULT_PATH_PATTERN).expect("default path pattern is valid"),
+        }
+    }
+}
+
+impl PartialEq for LinkDetector {
+    fn eq(&self, other: &Self) -> bool {
+        self.url.as_str() == other.url.as_str() && self.path.as_str() == other.path.as_str()
+    }
+}
+
+impl LinkDetector {
+    /// Create a detector from custom URL and path patterns
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern is invalid or the path pattern lacks a `path` group.
+    pub fn new(url_pattern: &str, path_pattern: &str) -> Result<Self, Error> {
+        let compile = |pattern| Regex::new(pattern).map_err(|e| Error::LinkPattern(e.to_string()));
+        let url = compile(url_pattern)?;
+        let path = compile(path_pattern)?;
+        if !path.capture_names().any(|name| name == Some("path")) {
+            return Err(Error::LinkPattern(
+                "path pattern needs a `path` group".to_string(),
+            ));
+        }
+        Ok(Self { url, path })
+    }
+
+    /// Find all links in the grid
+    #[must_use]
+    pub fn detect(&self, grid: &Grid) -> Vec<DetectedLink> {
+        let mut links = Vec::new();
+        let mut row = 0;
+        while row < grid.rows() {
+            let (text, cells, next) = logical_line(grid, row);
+            links.extend(self.detect_line(&text, &cells));
+            row = next;
+        }
+        links
+    }
+
+    /// Find the link covering a cell, if any
+    #[must_use]
+    pub fn link_at(&self, grid: &Grid, row: usize, col: usize) -> Option<DetectedLink> {
+        if row >= grid.rows() {
+            return None;
+        }
+        // Walk back to the first row of the soft-wrapped line
+        let mut first = row;
+        while first > 0 && grid.is_wrapped(first - 1) {
+            first -= 1;
+        }
+        let (text, cells, _) = logical_line(grid, first);
+        self.detect_line(&text, &cells)
+            .into_iter()
+            .find(|link| link.contains(row, col))
+    }
+
+    /// Find links in one logical line; `cells` maps each char of `text` to its cell
+    fn detect_line(&self, text: &str, cells: &[(usize, usize)]) -> Vec<DetectedLink> {
+        // Byte offset of each char, to map regex matches back to cells
+        let offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        let span = |start: usize, end: usize| {
+            let first = offsets.partition_point(|&i| i < start);
+            let last = offsets.partition_point(|&i| i < end) - 1;
+            (cells[first], cells[last])
+        };
+
+        let mut links = Vec::new();
+        let mut taken = Vec::new();
+        for m in self.url.find_iter(text) {
+            let url = trim_url(m.as_str());
+            if url.is_empty() {
+                continue;
+            }
+            let (start, end) = span(m.start(), m.start() + url.len());
+            taken.push(m.start()..m.start() + url.len());
+            links.push(DetectedLink {
+                target: LinkTarget::Url(url.to_string()),
+                start,
+                end,
+            });
+        }
+        for caps in self.path.captures_iter(text) {
+            let m = caps.get(0).expect("group 0 always matches");
+            // URLs win over paths inside them (e.g. `example.com:8080`)
+            if m.is_empty() || taken.iter().any(|r| r.start < m.end() && m.start() < r.end) {
+                continue;
+            }
+            let Some(path) = caps.name("path") else {
+                continue;
+            };
+            let number = |name| caps.name(name).and_then(|n| n.as_str().parse().ok());
+            let (start, end) = span(m.start(), m.end());
+            links.push(DetectedLink {
+                target: LinkTarget::Path {
+                    path: path.as_str().to_string(),
+                    line: number("line"),
+                    col: number("col"),
+                },
+                start,
+                end,
+            });
+        }
+        links.sort_by_key(|link| link.start);
+        links
+    }
+}
+
+/// Join soft-wrapped rows starting at `row` into one line of text
+///
+/// Returns the text, the cell of each char, and the row after the line.
+fn logical_line(grid: &Grid, mut row: usize) -> (String, Vec<(usize, usize)>, usize) {
+    let mut text = String::new();
+    let mut cells = Vec::new();
+    loop {
+        for col in 0..grid.cols() {
+            if let Some(cell) = grid.get(row, col) {
+                text.push(cell.c);
+                cells.push((row, col));
+            }
+        }
+        row += 1;
+        if !grid.is_wrapped(row - 1) || row >= grid.rows() {
+            return (text, cells, row);
+        }
+    }
+}
+
+/// Drop trailing punctuation that usually ends a sentence rather than a URL
+fn trim_url(url: &str) -> &str {
+    let mut url = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '"']);
+    // Keep a closing paren only if the URL opened one (e.g. Wikipedia links)
+    while url.ends_with(')') && url.matches('(').count() < url.matches(')').count() {
+        url = url[..url.len() - 1].trim_end_matches(['.', ',', ':', ';', '!', '?']);
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::Cell;
+
+    fn grid_with(lines: &[&str], cols: usize) -> Grid {
+        let mut grid = Grid::new(lines.len(), cols);
+        for (row, line) in lines.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                grid.set(row, col, Cell::new(c));
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_detect_url() {
+        let grid = grid_with(&["see https://example.com/docs. ok"], 40);
+        let links = LinkDetector::default().detect(&grid);
+        assert_eq!(
+            links,
+            vec![DetectedLink {
+                target: LinkTarget::Url("https://example.com/docs".to_string()),
+                start: (0, 4),
+                end: (0, 27),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_detect_path_with_position() {
+        let grid = grid_with(&["  --> src/main.rs:10:5", "error at ./lib.rs:3"], 30);
+        let links = LinkDetector::default().detect(&grid);
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            links[0].target,
+            LinkTarget::Path {
+                path: "src/main.rs".to_string(),
+                line: Some(10),
+                col: Some(5),
+            }
+        );
+        assert_eq!(links[0].start, (0, 6));
+        assert_eq!(links[0].end, (0, 21));
+        assert_eq!(
+            links[1].target,
+            LinkTarget::Path {
+                path: "./lib.rs".to_string(),
+                line: Some(3),
+                col: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_url_wins_over_path() {
+        let grid = grid_with(&["http://example.com:8080/x"], 30);
+        let links = LinkDetector::default().detect(&grid);
+        assert_eq!(links.len(), 1);
+        assert!(matches!(links[0].target, LinkTarget::Url(_)));
+    }
+
+    #[test]
+    fn test_link_across_soft_wrap() {
+        let mut grid = grid_with(&["x https://exa", "mple.com y"], 13);
+        grid.set_wrapped(0, true);
+        let detector = LinkDetector::default();
+        let link = detector.link_at(&grid, 1, 2).unwrap();
+        assert_eq!(
+            link.target,
+            LinkTarget::Url("https://example.com".to_string())
+        );
+        assert_eq!(link.start, (0, 2));
+        assert_eq!(link.end, (1, 7));
+        assert_eq!(detector.link_at(&grid, 1, 9), None);
+    }
+
+    #[test]
+    fn test_trim_url() {
+        assert_eq!(trim_url("https://a.b/c)."), "https://a.b/c");
+        assert_eq!(trim_url("https://a.b/W_(x)"), "https://a.b/W_(x)");
+        assert_eq!(trim_url("https://a.b/?q=1,"), "https://a.b/?q=1");
+    }
+
+    #[test]
+    fn test_custom_patterns() {
+        let detector =
+            LinkDetector::new(r"jira:\w+-\d+", r"(?P<path>\S+\.py) line (?P<line>\d+)").unwrap();
+        let grid = grid_with(&["jira:ABC-12 app.py line 7"], 30);
+        let links = detector.detect(&grid);
+        assert_eq!(links[0].target, LinkTarget::Url("jira:ABC-12".to_string()));
+        assert_eq!(
+            links[1].target,
+            LinkTarget::Path {
+                path: "app.py".to_string(),
+                line: Some(7),
+                col: None,
+            }
+        );
+        assert!(LinkDetector::new(DEFAULT_URL_PATTERN, r"\d+").is_err());
+        assert!(LinkDetector::new("(", DEFAULT_PATH_PATTERN).is_err());
+    }
+}
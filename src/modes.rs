@@ -0,0 +1,96 @@
+//! Terminal modes set by SM/RM (ANSI) and DECSET/DECRST (DEC private)
+
+/// Modes governing how output is written to the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct TermModes {
+    /// DECAWM (`?7`): wrap to the next line after the last column
+    pub autowrap: bool,
+    /// IRM (`4`): printed characters shift the rest of the line right
+    pub insert: bool,
+    /// DECOM (`?6`): cursor addressing is relative to the scrolling region
+    pub origin: bool,
+    /// DECSCNM (`?5`): default foreground and background are swapped
+    pub reverse_video: bool,
+    /// LNM (`20`): line feed also returns the carriage, Enter sends CR LF
+    pub newline: bool,
+    /// Synchronized output (`?2026`): the screen isn't shown until the frame ends
+    pub synchronized: bool,
+}
+
+impl Default for TermModes {
+    fn default() -> Self {
+        Self {
+            autowrap: true,
+            insert: false,
+            origin: false,
+            reverse_video: false,
+            newline: false,
+            synchronized: false,
+        }
+    }
+}
+
+impl TermModes {
+    /// Apply ANSI mode `mode`, returning `false` if it isn't one of these modes
+    pub fn set_ansi(&mut self, mode: u16, enabled: bool) -> bool {
+        match mode {
+            4 => self.insert = enabled,
+            20 => self.newline = enabled,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Current value of an ANSI mode, or `None` if `mode` isn't one (for DECRQM)
+    pub fn ansi(self, mode: u16) -> Option<bool> {
+        match mode {
+            4 => Some(self.insert),
+            20 => Some(self.newline),
+            _ => None,
+        }
+    }
+
+    /// Apply DEC private mode `mode`, returning `false` if it isn't one of these modes
+    pub fn set_private(&mut self, mode: u16, enabled: bool) -> bool {
+        match mode {
+            5 => self.reverse_video = enabled,
+            6 => self.origin = enabled,
+            7 => self.autowrap = enabled,
+            2026 => self.synchronized = enabled,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Current value of a DEC private mode, or `None` if `mode` isn't one (for DECRQM)
+    pub fn private(self, mode: u16) -> Option<bool> {
+        match mode {
+            5 => Some(self.reverse_video),
+            6 => Some(self.origin),
+            7 => Some(self.autowrap),
+            2026 => Some(self.synchronized),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_modes() {
+        let mut modes = TermModes::default();
+        assert_eq!(modes.private(7), Some(true));
+        assert!(modes.set_private(7, false));
+        assert!(modes.set_private(5, true));
+        assert!(modes.set_ansi(4, true));
+        assert!(!modes.set_ansi(7, true));
+        assert!(!modes.set_private(25, false));
+        assert!(!modes.autowrap && modes.reverse_video && modes.insert);
+        assert_eq!(modes.ansi(20), Some(false));
+        assert_eq!(modes.ansi(5), None);
+        assert_eq!(modes.private(1000), None);
+    }
+}
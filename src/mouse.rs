@@ -0,0 +1,369 @@
+//! Mouse reporting (xterm mouse tracking modes and encodings)
+
+use dioxus::html::geometry::{ClientPoint, PixelsRect};
+use dioxus::prelude::*;
+
+/// Which mouse events the application asked to receive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum MouseTracking {
+    #[default]
+    Off,
+    /// Mode 9: button presses only, no modifiers
+    X10,
+    /// Mode 1000: presses, releases and wheel
+    Normal,
+    /// Mode 1002: additionally motion while a button is held
+    ButtonEvent,
+    /// Mode 1003: additionally all motion
+    AnyEvent,
+}
+
+/// How mouse reports are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum MouseEncoding {
+    /// `CSI M Cb Cx Cy` with single bytes (coordinates up to 223)
+    #[default]
+    Default,
+    /// Mode 1005: like default, with UTF-8 encoded values
+    Utf8,
+    /// Mode 1006: `CSI < b ; x ; y M/m`
+    Sgr,
+    /// Mode 1015: `CSI b ; x ; y M`
+    Urxvt,
+}
+
+/// Mouse modes negotiated by the running application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct MouseModes {
+    pub tracking: MouseTracking,
+    pub encoding: MouseEncoding,
+}
+
+impl MouseModes {
+    /// Apply DEC private mode `mode`, returning `false` if it isn't a mouse mode
+    pub fn set_mode(&mut self, mode: u16, enabled: bool) -> bool {
+        let tracking = match mode {
+            9 => MouseTracking::X10,
+            1000 => MouseTracking::Normal,
+            1002 => MouseTracking::ButtonEvent,
+            1003 => MouseTracking::AnyEvent,
+            1005 | 1006 | 1015 => {
+                let encoding = match mode {
+                    1005 => MouseEncoding::Utf8,
+                    1006 => MouseEncoding::Sgr,
+                    _ => MouseEncoding::Urxvt,
+                };
+                if enabled {
+                    self.encoding = encoding;
+                } else if self.encoding == encoding {
+                    self.encoding = MouseEncoding::Default;
+                }
+                return true;
+            }
+            _ => return false,
+        };
+        if enabled {
+            self.tracking = tracking;
+        } else if self.tracking == tracking {
+            self.tracking = MouseTracking::Off;
+        }
+        true
+    }
+
+    /// Current value of a mouse mode, or `None` if `mode` isn't one (for DECRQM)
+    pub fn mode(self, mode: u16) -> Option<bool> {
+        Some(match mode {
+            9 => self.tracking == MouseTracking::X10,
+            1000 => self.tracking == MouseTracking::Normal,
+            1002 => self.tracking == MouseTracking::ButtonEvent,
+            1003 => self.tracking == MouseTracking::AnyEvent,
+            1005 => self.encoding == MouseEncoding::Utf8,
+            1006 => self.encoding == MouseEncoding::Sgr,
+            1015 => self.encoding == MouseEncoding::Urxvt,
+            _ => return None,
+        })
+    }
+
+    /// Whether the application wants mouse events at all
+    pub fn is_active(self) -> bool {
+        self.tracking != MouseTracking::Off
+    }
+}
+
+/// Mouse buttons as numbered by the xterm protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl MouseButton {
+    /// Map a Dioxus mouse button (only the three main buttons are reported)
+    pub fn from_dioxus(button: dioxus::html::input_data::MouseButton) -> Option<Self> {
+        use dioxus::html::input_data::MouseButton as Dx;
+        match button {
+            Dx::Primary => Some(Self::Left),
+            Dx::Auxiliary => Some(Self::Middle),
+            Dx::Secondary => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    const fn code(self) -> u32 {
+        match self {
+            Self::Left => 0,
+            Self::Middle => 1,
+            Self::Right => 2,
+            Self::WheelUp => 64,
+            Self::WheelDown => 65,
+            Self::WheelLeft => 66,
+            Self::WheelRight => 67,
+        }
+    }
+
+    const fn is_wheel(self) -> bool {
+        matches!(
+            self,
+            Self::WheelUp | Self::WheelDown | Self::WheelLeft | Self::WheelRight
+        )
+    }
+}
+
+/// Kind of mouse event being reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MouseEventKind {
+    Press(MouseButton),
+    Release(MouseButton),
+    /// Pointer moved, with the button held (if any)
+    Motion(Option<MouseButton>),
+}
+
+/// Mouse event data needed for encoding, in cell coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MouseInput {
+    pub kind: MouseEventKind,
+    pub row: usize,
+    pub col: usize,
+    pub modifiers: Modifiers,
+}
+
+/// Encode a mouse event as a report, or `None` if the application doesn't want it
+pub(crate) fn encode_mouse(input: &MouseInput, modes: MouseModes) -> Option<Vec<u8>> {
+    let (button, release, motion) = match input.kind {
+        MouseEventKind::Press(button) => (Some(button), false, false),
+        MouseEventKind::Release(button) if !button.is_wheel() => (Some(button), true, false),
+        MouseEventKind::Release(_) => return None,
+        MouseEventKind::Motion(button) => (button, false, true),
+    };
+    let wanted = match modes.tracking {
+        MouseTracking::Off => false,
+        MouseTracking::X10 => !release && !motion,
+        MouseTracking::Normal => !motion,
+        MouseTracking::ButtonEvent => !motion || button.is_some(),
+        MouseTracking::AnyEvent => true,
+    };
+    if !wanted {
+        return None;
+    }
+
+    let sgr = modes.encoding == MouseEncoding::Sgr;
+    // Non-SGR encodings can't say which button was released
+    let mut code = match button {
+        Some(button) if !release || sgr => button.code(),
+        _ => 3,
+    };
+    if motion {
+        code += 32;
+    }
+    if modes.tracking != MouseTracking::X10 {
+        if input.modifiers.shift() {
+            code += 4;
+        }
+        if input.modifiers.alt() || input.modifiers.meta() {
+            code += 8;
+        }
+        if input.modifiers.ctrl() {
+            code += 16;
+        }
+    }
+
+    let x = u32::try_from(input.col + 1).ok()?;
+    let y = u32::try_from(input.row + 1).ok()?;
+    match modes.encoding {
+        MouseEncoding::Sgr => {
+            let action = if release { 'm' } else { 'M' };
+            Some(format!("\x1b[<{code};{x};{y}{action}").into_bytes())
+        }
+        MouseEncoding::Urxvt => Some(format!("\x1b[{};{x};{y}M", code + 32).into_bytes()),
+        MouseEncoding::Utf8 => {
+            let mut report = String::from("\x1b[M");
+            for value in [code, x, y] {
+                // Values above 2015 don't fit in two UTF-8 bytes
+                if value + 32 > 2047 {
+                    return None;
+                }
+                report.push(char::from_u32(value + 32)?);
+            }
+            Some(report.into_bytes())
+        }
+        MouseEncoding::Default => {
+            let mut report = b"\x1b[M".to_vec();
+            for value in [code, x, y] {
+                report.push(u8::try_from(value + 32).ok()?);
+            }
+            Some(report)
+        }
+    }
+}
+
+/// Map a client-space point to a (row, col) cell within the grid's bounding box
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub(crate) fn cell_at(
+    point: ClientPoint,
+    rect: PixelsRect,
+    rows: usize,
+    cols: usize,
+) -> (usize, usize) {
+    let cell_width = rect.width() / cols as f64;
+    let cell_height = rect.height() / rows as f64;
+    if cell_width <= 0.0 || cell_height <= 0.0 {
+        return (0, 0);
+    }
+    let col = ((point.x - rect.min_x()) / cell_width).floor().max(0.0) as usize;
+    let row = ((point.y - rect.min_y()) / cell_height).floor().max(0.0) as usize;
+    (row.min(rows - 1), col.min(cols - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dioxus::html::geometry::euclid::{Point2D, Rect, Size2D};
+
+    fn input(kind: MouseEventKind, modifiers: Modifiers) -> MouseInput {
+        MouseInput {
+            kind,
+            row: 4,
+            col: 9,
+            modifiers,
+        }
+    }
+
+    fn modes(tracking: MouseTracking, encoding: MouseEncoding) -> MouseModes {
+        MouseModes { tracking, encoding }
+    }
+
+    #[test]
+    fn test_set_mode() {
+        let mut modes = MouseModes::default();
+        assert!(modes.set_mode(1002, true));
+        assert!(modes.set_mode(1006, true));
+        assert_eq!(modes.tracking, MouseTracking::ButtonEvent);
+        assert_eq!(modes.encoding, MouseEncoding::Sgr);
+        // Resetting a mode that isn't active leaves tracking alone
+        assert!(modes.set_mode(1000, false));
+        assert!(modes.is_active());
+        assert!(modes.set_mode(1002, false));
+        assert!(!modes.is_active());
+        assert!(!modes.set_mode(25, true));
+    }
+
+    #[test]
+    fn test_default_encoding() {
+        let press = input(MouseEventKind::Press(MouseButton::Left), Modifiers::empty());
+        let release = input(
+            MouseEventKind::Release(MouseButton::Left),
+            Modifiers::empty(),
+        );
+        let normal = modes(MouseTracking::Normal, MouseEncoding::Default);
+        assert_eq!(encode_mouse(&press, normal).unwrap(), b"\x1b[M *%");
+        assert_eq!(encode_mouse(&release, normal).unwrap(), b"\x1b[M#*%");
+    }
+
+    #[test]
+    fn test_sgr_encoding_with_modifiers() {
+        let sgr = modes(MouseTracking::Normal, MouseEncoding::Sgr);
+        let press = input(
+            MouseEventKind::Press(MouseButton::Right),
+            Modifiers::CONTROL,
+        );
+        assert_eq!(encode_mouse(&press, sgr).unwrap(), b"\x1b[<18;10;5M");
+        let release = input(
+            MouseEventKind::Release(MouseButton::Right),
+            Modifiers::empty(),
+        );
+        assert_eq!(encode_mouse(&release, sgr).unwrap(), b"\x1b[<2;10;5m");
+        let wheel = input(
+            MouseEventKind::Press(MouseButton::WheelDown),
+            Modifiers::empty(),
+        );
+        assert_eq!(encode_mouse(&wheel, sgr).unwrap(), b"\x1b[<65;10;5M");
+    }
+
+    #[test]
+    fn test_urxvt_and_utf8_encoding() {
+        let press = input(MouseEventKind::Press(MouseButton::Left), Modifiers::empty());
+        let urxvt = modes(MouseTracking::Normal, MouseEncoding::Urxvt);
+        assert_eq!(encode_mouse(&press, urxvt).unwrap(), b"\x1b[32;10;5M");
+
+        let mut far = press.clone();
+        far.col = 299;
+        let utf8 = modes(MouseTracking::Normal, MouseEncoding::Utf8);
+        assert_eq!(
+            encode_mouse(&far, utf8).unwrap(),
+            "\x1b[M \u{14c}%".as_bytes()
+        );
+        // Out of range for the single-byte encoding
+        let default = modes(MouseTracking::Normal, MouseEncoding::Default);
+        assert!(encode_mouse(&far, default).is_none());
+    }
+
+    #[test]
+    fn test_tracking_filters_events() {
+        let drag = input(
+            MouseEventKind::Motion(Some(MouseButton::Left)),
+            Modifiers::empty(),
+        );
+        let hover = input(MouseEventKind::Motion(None), Modifiers::empty());
+        let release = input(
+            MouseEventKind::Release(MouseButton::Left),
+            Modifiers::empty(),
+        );
+        let x10 = modes(MouseTracking::X10, MouseEncoding::Sgr);
+        let normal = modes(MouseTracking::Normal, MouseEncoding::Sgr);
+        let button = modes(MouseTracking::ButtonEvent, MouseEncoding::Sgr);
+        let any = modes(MouseTracking::AnyEvent, MouseEncoding::Sgr);
+
+        assert!(encode_mouse(&release, x10).is_none());
+        assert!(encode_mouse(&drag, normal).is_none());
+        assert_eq!(encode_mouse(&drag, button).unwrap(), b"\x1b[<32;10;5M");
+        assert!(encode_mouse(&hover, button).is_none());
+        assert_eq!(encode_mouse(&hover, any).unwrap(), b"\x1b[<35;10;5M");
+        assert!(encode_mouse(&hover, MouseModes::default()).is_none());
+    }
+
+    #[test]
+    fn test_x10_ignores_modifiers() {
+        let press = input(MouseEventKind::Press(MouseButton::Left), Modifiers::CONTROL);
+        let x10 = modes(MouseTracking::X10, MouseEncoding::Sgr);
+        assert_eq!(encode_mouse(&press, x10).unwrap(), b"\x1b[<0;10;5M");
+    }
+
+    #[test]
+    fn test_cell_at() {
+        let rect = Rect::new(Point2D::new(10.0, 20.0), Size2D::new(800.0, 240.0));
+        assert_eq!(cell_at(Point2D::new(10.0, 20.0), rect, 24, 80), (0, 0));
+        assert_eq!(cell_at(Point2D::new(35.0, 45.0), rect, 24, 80), (2, 2));
+        // Points outside the grid are clamped
+        assert_eq!(cell_at(Point2D::new(0.0, 0.0), rect, 24, 80), (0, 0));
+        assert_eq!(cell_at(Point2D::new(2000.0, 900.0), rect, 24, 80), (23, 79));
+    }
+}
@@ -0,0 +1,155 @@
+//! Desktop notifications requested by applications (OSC 9 / OSC 777)
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::blocks::CommandBlock;
+
+/// A notification to show to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// Title, if the application gave one
+    pub title: Option<String>,
+    /// Message text
+    pub body: String,
+}
+
+impl Notification {
+    /// Create a notification
+    #[must_use]
+    pub fn new(title: Option<String>, body: impl Into<String>) -> Self {
+        Self {
+            title,
+            body: body.into(),
+        }
+    }
+
+    /// Notification for a finished command, naming its status and duration
+    #[must_use]
+    pub fn command_finished(block: &CommandBlock) -> Self {
+        let title = if block.failed() {
+            "Command failed"
+        } else {
+            "Command finished"
+        };
+        let mut body = if block.command.is_empty() {
+            "Command".to_string()
+        } else {
+            format!("`{}`", block.command)
+        };
+        if let Some(code) = block.exit_code {
+            let _ = write!(body, " exited with {code}");
+        } else {
+            body.push_str(" finished");
+        }
+        if let Some(duration) = block.duration {
+            let _ = write!(body, " after {}", format_duration(duration));
+        }
+        Self::new(Some(title.to_string()), body)
+    }
+}
+
+/// Parse OSC 9 data (`message`); ConEmu-style numeric subcommands are ignored
+pub(crate) fn parse_osc9(data: &str) -> Option<Notification> {
+    if let Some((command, _)) = data.split_once(';') {
+        if !command.is_empty() && command.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+    }
+    let body = clean(data);
+    (!body.is_empty()).then(|| Notification::new(None, body))
+}
+
+/// Parse OSC 777 data (`notify;title;body`)
+pub(crate) fn parse_osc777(data: &str) -> Option<Notification> {
+    let mut parts = data.splitn(3, ';');
+    if parts.next()? != "notify" {
+        return None;
+    }
+    let title = clean(parts.next()?);
+    let body = clean(parts.next().unwrap_or_default());
+    Some(Notification::new(
+        (!title.is_empty()).then_some(title),
+        body,
+    ))
+}
+
+/// Strip control characters from notification text
+fn clean(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Format a duration as `1h 2m 3s`, `2m 3s` or `3s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc9() {
+        assert_eq!(
+            parse_osc9("Build done"),
+            Some(Notification::new(None, "Build done"))
+        );
+        assert_eq!(
+            parse_osc9("done; 3 warnings"),
+            Some(Notification::new(None, "done; 3 warnings"))
+        );
+        // ConEmu progress reports are not notifications
+        assert_eq!(parse_osc9("4;1;50"), None);
+        assert_eq!(parse_osc9(""), None);
+    }
+
+    #[test]
+    fn test_parse_osc777() {
+        assert_eq!(
+            parse_osc777("notify;cargo;Build finished; 0 errors"),
+            Some(Notification::new(
+                Some("cargo".to_string()),
+                "Build finished; 0 errors"
+            ))
+        );
+        assert_eq!(
+            parse_osc777("notify;;body"),
+            Some(Notification::new(None, "body"))
+        );
+        assert_eq!(parse_osc777("other;x;y"), None);
+    }
+
+    #[test]
+    fn test_command_finished() {
+        use crate::blocks::CommandTracker;
+        use crate::term::{Cell, Grid};
+
+        let mut grid = Grid::new(2, 10);
+        for (col, c) in "$ make".chars().enumerate() {
+            grid.set(0, col, Cell::new(c));
+        }
+        let mut tracker = CommandTracker::default();
+        tracker.prompt_start(0);
+        tracker.command_start(0, 2);
+        tracker.output_start(&grid, 0, (1, 0));
+        tracker.command_end(1, 0, Some(2));
+        let notification = Notification::command_finished(&tracker.blocks()[0]);
+        assert_eq!(notification.title.as_deref(), Some("Command failed"));
+        assert_eq!(notification.body, "`make` exited with 2 after 0s");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "5s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1h 2m 5s");
+    }
+}
@@ -1,17 +1,25 @@
 //! PTY (pseudo-terminal) management
 
-use portable_pty::{CommandBuilder, PtySize, native_pty_system};
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize, native_pty_system};
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+use crate::integration::{self, Injection};
 use crate::{Error, Result};
 
 /// PTY handle for terminal I/O
 pub struct Pty {
     writer: Arc<Mutex<Box<dyn Write + Send>>>,
     reader_rx: mpsc::Receiver<Vec<u8>>,
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    child_pid: Option<u32>,
+    started: Instant,
     size: PtySize,
+    exited: bool,
 }
 
 impl std::fmt::Debug for Pty {
@@ -36,6 +44,36 @@ impl Pty {
     ///
     /// Returns an error if the PTY cannot be created or the command fails to spawn.
     pub fn spawn(command: &str, args: &[&str], rows: u16, cols: u16) -> Result<Self> {
+        Self::spawn_inner(command, args, rows, cols, None)
+    }
+
+    /// Like [`Pty::spawn`], but loads the bundled shell integration into bash, zsh
+    /// or fish so they report prompts (OSC 133) and the working directory (OSC 7)
+    ///
+    /// Only shells started without arguments are integrated; other commands are
+    /// spawned unchanged. The user's dotfiles are still loaded and never modified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PTY cannot be created or the command fails to spawn.
+    pub fn spawn_with_shell_integration(
+        command: &str,
+        args: &[&str],
+        rows: u16,
+        cols: u16,
+    ) -> Result<Self> {
+        // Without the scripts the shell still works, just without integration
+        let injection = integration::prepare(command, args).ok().flatten();
+        Self::spawn_inner(command, args, rows, cols, injection)
+    }
+
+    fn spawn_inner(
+        command: &str,
+        args: &[&str],
+        rows: u16,
+        cols: u16,
+        injection: Option<Injection>,
+    ) -> Result<Self> {
         if rows == 0 || cols == 0 {
             return Err(Error::InvalidSize { rows, cols });
         }
@@ -54,11 +92,18 @@ impl Pty {
 
         let mut cmd = CommandBuilder::new(command);
         cmd.args(args);
+        if let Some(injection) = injection {
+            cmd.args(&injection.args);
+            for (key, value) in &injection.env {
+                cmd.env(key, value);
+            }
+        }
 
-        let _child = pair
+        let child = pair
             .slave
             .spawn_command(cmd)
             .map_err(|e| Error::SpawnCommand(e.to_string()))?;
+        let child_pid = child.process_id();
 
         let writer = pair
             .master
@@ -89,7 +134,12 @@ impl Pty {
         Ok(Self {
             writer: Arc::new(Mutex::new(writer)),
             reader_rx: rx,
+            master: pair.master,
+            child,
+            child_pid,
+            started: Instant::now(),
             size,
+            exited: false,
         })
     }
 
@@ -120,7 +170,32 @@ impl Pty {
 
     /// Try to receive output from the PTY (non-blocking)
     pub fn try_read(&mut self) -> Option<Vec<u8>> {
-        self.reader_rx.try_recv().ok()
+        match self.reader_rx.try_recv() {
+            Ok(data) => Some(data),
+            Err(mpsc::error::TryRecvError::Disconnected) => {
+                self.exited = true;
+                None
+            }
+            Err(mpsc::error::TryRecvError::Empty) => None,
+        }
+    }
+
+    /// Whether the command exited and all of its output has been read
+    #[must_use]
+    pub const fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Exit code of the command, once it has exited
+    pub fn exit_code(&mut self) -> Option<i32> {
+        let status = self.child.try_wait().ok()??;
+        Some(i32::try_from(status.exit_code()).unwrap_or(i32::MAX))
+    }
+
+    /// Time since the command was spawned
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
     }
 
     /// Receive output from the PTY (async)
@@ -143,6 +218,37 @@ impl Pty {
         Ok(())
     }
 
+    /// Process id of the spawned command
+    #[must_use]
+    pub const fn child_pid(&self) -> Option<u32> {
+        self.child_pid
+    }
+
+    /// Process id of the foreground process (e.g. an editor started from the shell)
+    ///
+    /// Falls back to the spawned command where the foreground process group is unknown.
+    #[must_use]
+    pub fn foreground_pid(&self) -> Option<u32> {
+        #[cfg(unix)]
+        if let Some(pid) = self.master.process_group_leader() {
+            return u32::try_from(pid).ok();
+        }
+        self.child_pid
+    }
+
+    /// Working directory of the foreground process, read from `/proc`
+    ///
+    /// Returns `None` on platforms without `/proc` or if the process has exited.
+    #[must_use]
+    pub fn cwd(&self) -> Option<PathBuf> {
+        if cfg!(target_os = "linux") {
+            let pid = self.foreground_pid()?;
+            std::fs::read_link(format!("/proc/{pid}/cwd")).ok()
+        } else {
+            None
+        }
+    }
+
     /// Get current terminal size
     #[must_use]
     pub fn size(&self) -> (u16, u16) {
@@ -182,6 +288,45 @@ mod tests {
         assert_eq!(pty.size(), (24, 80));
     }
 
+    #[test]
+    fn test_child_pid() {
+        let pty = Pty::spawn("sleep", &["1"], 24, 80).unwrap();
+        assert!(pty.child_pid().is_some());
+        assert!(pty.foreground_pid().is_some());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_cwd_from_proc() {
+        let pty = Pty::spawn("sleep", &["1"], 24, 80).unwrap();
+        assert!(pty.cwd().is_some_and(|cwd| cwd.is_absolute()));
+    }
+
+    #[test]
+    fn test_spawn_with_shell_integration() {
+        // Commands with arguments are spawned without writing any scripts
+        assert!(Pty::spawn_with_shell_integration("echo", &["test"], 24, 80).is_ok());
+    }
+
+    #[test]
+    #[ignore = "spawns bash and writes the scripts to the runtime directory"]
+    fn test_spawn_bash_with_shell_integration() {
+        assert!(Pty::spawn_with_shell_integration("bash", &[], 24, 80).is_ok());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut pty = Pty::spawn("sh", &["-c", "exit 3"], 24, 80).unwrap();
+        for _ in 0..200 {
+            if pty.try_read().is_none() && pty.has_exited() && pty.exit_code().is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(pty.has_exited());
+        assert_eq!(pty.exit_code(), Some(3));
+    }
+
     #[test]
     fn test_resize_valid() {
         let mut pty = Pty::spawn("echo", &["test"], 24, 80).unwrap();
@@ -0,0 +1,363 @@
+//! Sixel graphics decoder (DCS `q`)
+//!
+//! Sixel data is decoded as it streams in, so images are not limited by the
+//! buffer kept for other DCS sequences.
+
+use crate::graphics::Image;
+
+/// VT340 default palette, as RGB percentages
+const VT340_PALETTE: [(u32, u32, u32); 16] = [
+    (0, 0, 0),
+    (20, 20, 80),
+    (80, 13, 13),
+    (20, 80, 20),
+    (80, 20, 80),
+    (20, 80, 80),
+    (80, 80, 20),
+    (53, 53, 53),
+    (26, 26, 26),
+    (33, 33, 60),
+    (60, 26, 26),
+    (33, 60, 33),
+    (60, 33, 60),
+    (33, 60, 60),
+    (60, 60, 33),
+    (80, 80, 80),
+];
+
+/// Number of color registers
+const PALETTE_SIZE: usize = 256;
+
+/// Command whose numeric parameters are being read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    /// `!Pn` - repeat the next sixel
+    Repeat,
+    /// `#Pc;Pu;Px;Py;Pz` - select or define a color
+    Color,
+    /// `"Pan;Pad;Ph;Pv` - raster attributes
+    Raster,
+}
+
+/// Streaming Sixel decoder producing an RGBA image
+#[derive(Debug)]
+pub(crate) struct SixelDecoder {
+    palette: Vec<[u8; 4]>,
+    color: usize,
+    // Color of pixels never drawn (transparent when P2 is 1)
+    background: [u8; 4],
+    command: Option<Command>,
+    params: Vec<u32>,
+    repeat: u32,
+    x: usize,
+    y: usize,
+    // Image size: the raster attributes, grown to cover everything drawn
+    width: usize,
+    height: usize,
+    // Pixel buffer with its allocated size
+    pixels: Vec<u8>,
+    stride: usize,
+    capacity_rows: usize,
+    // Largest buffer allowed, in bytes; larger images are discarded
+    max_bytes: usize,
+    failed: bool,
+}
+
+impl SixelDecoder {
+    /// Start decoding a sequence with DCS parameters `params` (`P1;P2;P3`)
+    ///
+    /// With `P2` = 1 unset pixels stay transparent; otherwise they are filled
+    /// with color register 0 like on a VT340.
+    pub fn new(params: &[u8], max_bytes: usize) -> Self {
+        let transparent = params
+            .split(|&b| b == b';')
+            .nth(1)
+            .and_then(|p| std::str::from_utf8(p).ok())
+            .and_then(|p| p.parse::<u32>().ok())
+            == Some(1);
+        let mut palette = vec![[0, 0, 0, 255]; PALETTE_SIZE];
+        for (entry, &(r, g, b)) in palette.iter_mut().zip(VT340_PALETTE.iter()) {
+            *entry = [percent(r), percent(g), percent(b), 255];
+        }
+        let background = if transparent { [0; 4] } else { palette[0] };
+        Self {
+            palette,
+            color: 0,
+            background,
+            command: None,
+            params: Vec::new(),
+            repeat: 1,
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+            stride: 0,
+            capacity_rows: 0,
+            max_bytes,
+            failed: false,
+        }
+    }
+
+    /// Feed one byte of Sixel data
+    pub fn feed(&mut self, byte: u8) {
+        if self.failed {
+            return;
+        }
+        if self.command.is_some() {
+            match byte {
+                b'0'..=b'9' => {
+                    let digit = u32::from(byte - b'0');
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    if let Some(param) = self.params.last_mut() {
+                        *param = param.saturating_mul(10).saturating_add(digit);
+                    }
+                    return;
+                }
+                b';' => {
+                    if self.params.is_empty() {
+                        self.params.push(0);
+                    }
+                    self.params.push(0);
+                    return;
+                }
+                _ => self.finish_command(),
+            }
+        }
+        match byte {
+            b'!' => self.start_command(Command::Repeat),
+            b'#' => self.start_command(Command::Color),
+            b'"' => self.start_command(Command::Raster),
+            // Graphics carriage return
+            b'$' => self.x = 0,
+            // Graphics new line: next band of six rows
+            b'-' => {
+                self.x = 0;
+                self.y += 6;
+            }
+            0x3f..=0x7e => {
+                let repeat = std::mem::replace(&mut self.repeat, 1) as usize;
+                self.draw(byte - 0x3f, repeat);
+            }
+            _ => {}
+        }
+    }
+
+    /// Finish decoding, returning the image if anything was drawn
+    pub fn finish(mut self) -> Option<Image> {
+        if self.command.is_some() {
+            self.finish_command();
+        }
+        if self.failed || self.width == 0 || self.height == 0 {
+            return None;
+        }
+        if !self.reserve(self.width, self.height) {
+            return None;
+        }
+        let mut rgba = Vec::with_capacity(self.width * self.height * 4);
+        for row in 0..self.height {
+            let start = row * self.stride * 4;
+            rgba.extend_from_slice(&self.pixels[start..start + self.width * 4]);
+        }
+        Some(Image::new(
+            u32::try_from(self.width).ok()?,
+            u32::try_from(self.height).ok()?,
+            rgba,
+        ))
+    }
+
+    fn start_command(&mut self, command: Command) {
+        self.command = Some(command);
+        self.params.clear();
+    }
+
+    fn finish_command(&mut self) {
+        let param = |i: usize| self.params.get(i).copied().unwrap_or(0);
+        match self.command.take() {
+            Some(Command::Repeat) => self.repeat = param(0).max(1),
+            Some(Command::Color) => {
+                let index = param(0) as usize % PALETTE_SIZE;
+                if self.params.len() >= 5 {
+                    let (x, y, z) = (param(2), param(3), param(4));
+                    let rgb = match param(1) {
+                        1 => Some(hls_to_rgb(x, y, z)),
+                        2 => Some([percent(x), percent(y), percent(z)]),
+                        _ => None,
+                    };
+                    if let Some([r, g, b]) = rgb {
+                        self.palette[index] = [r, g, b, 255];
+                    }
+                }
+                self.color = index;
+            }
+            Some(Command::Raster) => {
+                let (width, height) = (param(2) as usize, param(3) as usize);
+                if width > 0 && height > 0 && self.reserve(width, height) {
+                    self.width = self.width.max(width);
+                    self.height = self.height.max(height);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Draw a sixel (six vertical pixels, low bit on top) `repeat` times
+    fn draw(&mut self, bits: u8, repeat: usize) {
+        let (x, y) = (self.x, self.y);
+        self.x = x.saturating_add(repeat);
+        if !self.reserve(self.x, y + 6) {
+            self.failed = true;
+            return;
+        }
+        self.width = self.width.max(self.x);
+        if bits != 0 {
+            // Only rows up to the lowest set bit count towards the height
+            self.height = self.height.max(y + 8 - bits.leading_zeros() as usize);
+        }
+        let color = self.palette[self.color];
+        for bit in 0..6 {
+            if bits & (1 << bit) != 0 {
+                let start = ((y + bit) * self.stride + x) * 4;
+                for pixel in self.pixels[start..start + repeat * 4].chunks_exact_mut(4) {
+                    pixel.copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    /// Grow the buffer to hold `width` x `height` pixels, if within the limit
+    fn reserve(&mut self, width: usize, height: usize) -> bool {
+        if width <= self.stride && height <= self.capacity_rows {
+            return true;
+        }
+        let stride = if width > self.stride {
+            width.max(self.stride * 2).max(64)
+        } else {
+            self.stride
+        };
+        let rows = if height > self.capacity_rows {
+            height.max(self.capacity_rows * 2).max(6)
+        } else {
+            self.capacity_rows
+        };
+        // Only what is needed counts against the limit, not the headroom
+        if width.saturating_mul(height).saturating_mul(4) > self.max_bytes {
+            return false;
+        }
+        let (stride, rows) = if stride.saturating_mul(rows).saturating_mul(4) > self.max_bytes {
+            (width.max(self.stride), height.max(self.capacity_rows))
+        } else {
+            (stride, rows)
+        };
+        let mut pixels = self.background.repeat(stride * rows);
+        for row in 0..self.capacity_rows {
+            let old = row * self.stride * 4;
+            let new = row * stride * 4;
+            pixels[new..new + self.stride * 4]
+                .copy_from_slice(&self.pixels[old..old + self.stride * 4]);
+        }
+        self.pixels = pixels;
+        self.stride = stride;
+        self.capacity_rows = rows;
+        true
+    }
+}
+
+/// Scale a percentage (0-100) to a color channel
+fn percent(value: u32) -> u8 {
+    u8::try_from(value.min(100) * 255 / 100).unwrap_or(u8::MAX)
+}
+
+/// Convert a Sixel HLS color (hue in degrees with blue at 0, percentages) to RGB
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn hls_to_rgb(hue: u32, lightness: u32, saturation: u32) -> [u8; 3] {
+    // Sixel puts blue at 0 degrees, red at 120 and green at 240
+    let hue = f64::from((hue + 240) % 360) / 360.0;
+    let lightness = f64::from(lightness.min(100)) / 100.0;
+    let saturation = f64::from(saturation.min(100)) / 100.0;
+    if saturation == 0.0 {
+        let v = (lightness * 255.0).round() as u8;
+        return [v, v, v];
+    }
+    let q = if lightness < 0.5 {
+        lightness * (1.0 + saturation)
+    } else {
+        lightness + saturation - lightness * saturation
+    };
+    let p = 2.0 * lightness - q;
+    let channel = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+    [
+        channel(hue + 1.0 / 3.0),
+        channel(hue),
+        channel(hue - 1.0 / 3.0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(params: &[u8], data: &[u8]) -> Option<Image> {
+        let mut decoder = SixelDecoder::new(params, 1 << 20);
+        for &byte in data {
+            decoder.feed(byte);
+        }
+        decoder.finish()
+    }
+
+    fn pixel(image: &Image, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * image.width as usize + x) * 4;
+        image.rgba[i..i + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_decode_colors_and_repeat() {
+        // Red full column, then 3 green columns with only the top pixel set
+        let image = decode(b"0;1", b"#1;2;100;0;0#1~#2;2;0;100;0!3@").unwrap();
+        assert_eq!((image.width, image.height), (4, 6));
+        assert_eq!(pixel(&image, 0, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 3, 0), [0, 255, 0, 255]);
+        // Transparent background with P2 = 1
+        assert_eq!(pixel(&image, 3, 1), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decode_bands_and_raster() {
+        let image = decode(b"", b"\"1;1;3;8#1;2;0;0;100~$-@").unwrap();
+        assert_eq!((image.width, image.height), (3, 8));
+        assert_eq!(pixel(&image, 0, 6), [0, 0, 255, 255]);
+        // Unset pixels take color register 0
+        assert_eq!(pixel(&image, 2, 0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        assert!(decode(b"", b"").is_none());
+        let mut decoder = SixelDecoder::new(b"", 100);
+        for &byte in b"!1000~" {
+            decoder.feed(byte);
+        }
+        assert!(decoder.finish().is_none());
+    }
+
+    #[test]
+    fn test_hls_to_rgb() {
+        assert_eq!(hls_to_rgb(120, 50, 100), [255, 0, 0]);
+        assert_eq!(hls_to_rgb(0, 50, 100), [0, 0, 255]);
+        assert_eq!(hls_to_rgb(0, 100, 0), [255, 255, 255]);
+    }
+}
@@ -1,5 +1,6 @@
 //! Terminal emulation types using `alacritty_terminal`
 
+use std::sync::Arc;
 use vte::ansi::Rgb;
 
 /// A single cell in the terminal grid
@@ -13,6 +14,8 @@ pub struct Cell {
     pub bg: Color,
     /// Text style
     pub style: Style,
+    /// Hyperlink this cell belongs to (OSC 8)
+    pub link: Option<Arc<Hyperlink>>,
 }
 
 impl Default for Cell {
@@ -22,6 +25,7 @@ impl Default for Cell {
             fg: Color::default_fg(),
             bg: Color::default_bg(),
             style: Style::default(),
+            link: None,
         }
     }
 }
@@ -44,6 +48,7 @@ impl Cell {
             fg,
             bg,
             style: Style::default(),
+            link: None,
         }
     }
 
@@ -54,6 +59,43 @@ impl Cell {
     }
 }
 
+/// Hyperlink set by an application with OSC 8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperlink {
+    /// Application-supplied id; cells sharing an id and URI are one link
+    pub id: Option<String>,
+    /// Link target
+    pub uri: String,
+}
+
+impl Hyperlink {
+    /// Create a hyperlink
+    #[must_use]
+    pub fn new(id: Option<String>, uri: impl Into<String>) -> Self {
+        Self {
+            id,
+            uri: uri.into(),
+        }
+    }
+
+    /// Parse the parameters and URI of an OSC 8 sequence (`params;uri`)
+    ///
+    /// Returns `None` for an empty URI, which ends the current link.
+    #[must_use]
+    pub fn from_osc8(data: &str) -> Option<Self> {
+        let (params, uri) = data.split_once(';')?;
+        if uri.is_empty() {
+            return None;
+        }
+        let id = params
+            .split(':')
+            .find_map(|param| param.strip_prefix("id="))
+            .filter(|id| !id.is_empty())
+            .map(str::to_string);
+        Some(Self::new(id, uri))
+    }
+}
+
 /// RGB color representation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
@@ -92,6 +134,61 @@ impl Color {
     pub fn to_hex(&self) -> String {
         format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
     }
+
+    /// Parse an X11 color spec as used by OSC color sequences
+    ///
+    /// Accepts `rgb:R/G/B` with 1-4 hex digits per channel and `#RGB` forms
+    /// with 1-4 digits per channel.
+    #[must_use]
+    pub fn from_xparse(spec: &str) -> Option<Self> {
+        // Scale a channel of `digits` hex digits to 8 bits
+        let channel = |hex: &str| {
+            if hex.is_empty() || hex.len() > 4 {
+                return None;
+            }
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            let max = (1u32 << (4 * hex.len())) - 1;
+            u8::try_from((value * 255 + max / 2) / max).ok()
+        };
+        if let Some(rgb) = spec.strip_prefix("rgb:") {
+            let mut parts = rgb.split('/');
+            let (r, g, b) = (parts.next()?, parts.next()?, parts.next()?);
+            if parts.next().is_some() {
+                return None;
+            }
+            return Some(Self::new(channel(r)?, channel(g)?, channel(b)?));
+        }
+        let hex = spec.strip_prefix('#')?;
+        if hex.is_empty() || hex.len() % 3 != 0 || hex.len() > 12 || !hex.is_ascii() {
+            return None;
+        }
+        let n = hex.len() / 3;
+        // `#RGB` digits are the high bits of each channel, unlike `rgb:`
+        let high = |part: &str| {
+            let value = u32::from_str_radix(part, 16).ok()?;
+            let bits = 4 * part.len();
+            let scaled = if bits >= 8 {
+                value >> (bits - 8)
+            } else {
+                value << (8 - bits)
+            };
+            u8::try_from(scaled).ok()
+        };
+        Some(Self::new(
+            high(&hex[..n])?,
+            high(&hex[n..2 * n])?,
+            high(&hex[2 * n..])?,
+        ))
+    }
+
+    /// Format as an X11 `rgb:rrrr/gggg/bbbb` spec, as xterm reports colors
+    #[must_use]
+    pub fn to_xparse(&self) -> String {
+        format!(
+            "rgb:{0:02x}{0:02x}/{1:02x}{1:02x}/{2:02x}{2:02x}",
+            self.r, self.g, self.b
+        )
+    }
 }
 
 impl From<Rgb> for Color {
@@ -155,10 +252,106 @@ impl Style {
     }
 }
 
+/// Cursor shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorShape {
+    /// Full cell block (default)
+    #[default]
+    Block,
+    /// Line under the cell
+    Underline,
+    /// Vertical bar at the left edge of the cell
+    Bar,
+}
+
+impl CursorShape {
+    /// Name used in CSS class names
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Underline => "underline",
+            Self::Bar => "bar",
+        }
+    }
+}
+
+/// Cursor appearance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CursorStyle {
+    /// Cursor shape
+    pub shape: CursorShape,
+    /// Whether the cursor blinks
+    pub blinking: bool,
+}
+
+impl CursorStyle {
+    /// Create a cursor style
+    #[must_use]
+    pub const fn new(shape: CursorShape, blinking: bool) -> Self {
+        Self { shape, blinking }
+    }
+
+    /// Style selected by DECSCUSR (`CSI n SP q`), or `None` for 0 (the default style)
+    #[must_use]
+    pub const fn from_decscusr(n: u16) -> Option<Self> {
+        let shape = match n {
+            0 => return None,
+            1 | 2 => CursorShape::Block,
+            3 | 4 => CursorShape::Underline,
+            _ => CursorShape::Bar,
+        };
+        Some(Self::new(shape, n % 2 == 1))
+    }
+}
+
+/// Semantic prompt mark set by shell integration (OSC 133)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMark {
+    /// Prompt is about to be drawn (`A`)
+    PromptStart,
+    /// Prompt ended, the user types the command (`B`)
+    CommandStart,
+    /// Command was submitted, its output follows (`C`)
+    OutputStart,
+    /// Command finished, with its exit code if reported (`D`)
+    CommandEnd(Option<i32>),
+}
+
+/// Line size set by DECSWL, DECDWL and DECDHL (`ESC # 5/6/3/4`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineSize {
+    /// Normal width and height
+    #[default]
+    Single,
+    /// Each character is twice as wide
+    DoubleWidth,
+    /// Top half of a double-width, double-height line
+    DoubleHeightTop,
+    /// Bottom half of a double-width, double-height line
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    /// Whether characters on the line are twice as wide (so only half the columns fit)
+    #[must_use]
+    pub const fn is_double_width(self) -> bool {
+        !matches!(self, Self::Single)
+    }
+}
+
 /// Terminal grid containing all cells
 #[derive(Debug, Clone)]
 pub struct Grid {
     cells: Vec<Cell>,
+    // Rows that continue onto the next row (soft-wrapped)
+    wrapped: Vec<bool>,
+    // Shell integration marks on each row
+    marks: Vec<Vec<PromptMark>>,
+    // Columns with a tab stop
+    tab_stops: Vec<bool>,
+    // Double-width / double-height attribute of each row
+    line_sizes: Vec<LineSize>,
     rows: usize,
     cols: usize,
 }
@@ -176,6 +369,10 @@ impl Grid {
 
         Self {
             cells: vec![Cell::default(); rows * cols],
+            wrapped: vec![false; rows],
+            marks: vec![Vec::new(); rows],
+            tab_stops: default_tab_stops(cols),
+            line_sizes: vec![LineSize::Single; rows],
             rows,
             cols,
         }
@@ -219,11 +416,122 @@ impl Grid {
         }
     }
 
+    /// Insert `count` blank cells at a position, shifting the rest of the row right
+    ///
+    /// Cells pushed past the last column are lost.
+    pub fn insert_cells(&mut self, row: usize, col: usize, count: usize) {
+        if row >= self.rows || col >= self.cols {
+            return;
+        }
+        let line = &mut self.cells[row * self.cols + col..(row + 1) * self.cols];
+        let count = count.min(line.len());
+        line.rotate_right(count);
+        line[..count].fill(Cell::default());
+    }
+
+    /// Check whether a row soft-wraps onto the next one
+    #[must_use]
+    pub fn is_wrapped(&self, row: usize) -> bool {
+        self.wrapped.get(row).copied().unwrap_or(false)
+    }
+
+    /// Mark whether a row soft-wraps onto the next one
+    pub fn set_wrapped(&mut self, row: usize, wrapped: bool) {
+        if let Some(flag) = self.wrapped.get_mut(row) {
+            *flag = wrapped;
+        }
+    }
+
+    /// Size attribute of a row
+    #[must_use]
+    pub fn line_size(&self, row: usize) -> LineSize {
+        self.line_sizes.get(row).copied().unwrap_or_default()
+    }
+
+    /// Set the size attribute of a row
+    pub fn set_line_size(&mut self, row: usize, size: LineSize) {
+        if let Some(slot) = self.line_sizes.get_mut(row) {
+            *slot = size;
+        }
+    }
+
+    /// Shell integration marks on a row
+    #[must_use]
+    pub fn marks(&self, row: usize) -> &[PromptMark] {
+        self.marks.get(row).map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a shell integration mark to a row
+    pub fn add_mark(&mut self, row: usize, mark: PromptMark) {
+        if let Some(marks) = self.marks.get_mut(row) {
+            marks.push(mark);
+        }
+    }
+
+    /// Replace the shell integration marks of a row
+    pub fn set_marks(&mut self, row: usize, marks: Vec<PromptMark>) {
+        if let Some(slot) = self.marks.get_mut(row) {
+            *slot = marks;
+        }
+    }
+
+    /// Rows with a mark, such as prompts for jump-to-prompt
+    pub fn rows_with_mark(&self, mark: PromptMark) -> impl Iterator<Item = usize> + '_ {
+        self.marks
+            .iter()
+            .enumerate()
+            .filter(move |(_, marks)| marks.contains(&mark))
+            .map(|(row, _)| row)
+    }
+
+    /// Check whether a column has a tab stop
+    #[must_use]
+    pub fn is_tab_stop(&self, col: usize) -> bool {
+        self.tab_stops.get(col).copied().unwrap_or(false)
+    }
+
+    /// Set or clear the tab stop at a column
+    pub fn set_tab_stop(&mut self, col: usize, stop: bool) {
+        if let Some(flag) = self.tab_stops.get_mut(col) {
+            *flag = stop;
+        }
+    }
+
+    /// Clear all tab stops
+    pub fn clear_tab_stops(&mut self) {
+        self.tab_stops.fill(false);
+    }
+
+    /// Restore the default tab stops (every 8 columns)
+    pub fn reset_tab_stops(&mut self) {
+        self.tab_stops = default_tab_stops(self.cols);
+    }
+
+    /// Column of the next tab stop after `col`, or the last column if there is none
+    #[must_use]
+    pub fn next_tab_stop(&self, col: usize) -> usize {
+        (col + 1..self.cols)
+            .find(|&c| self.tab_stops[c])
+            .unwrap_or(self.cols - 1)
+    }
+
+    /// Column of the previous tab stop before `col`, or the first column if there is none
+    #[must_use]
+    pub fn prev_tab_stop(&self, col: usize) -> usize {
+        (0..col.min(self.cols))
+            .rev()
+            .find(|&c| self.tab_stops[c])
+            .unwrap_or(0)
+    }
+
     /// Clear the entire grid
     pub fn clear(&mut self) {
         for cell in &mut self.cells {
             *cell = Cell::default();
         }
+        self.wrapped.fill(false);
+        self.marks.iter_mut().for_each(Vec::clear);
+        self.line_sizes.fill(LineSize::Single);
     }
 
     /// Iterate over rows
@@ -249,11 +557,20 @@ impl Grid {
         }
 
         self.cells = new_cells;
+        self.wrapped.resize(new_rows, false);
+        self.marks.resize(new_rows, Vec::new());
+        self.line_sizes.resize(new_rows, LineSize::Single);
+        self.tab_stops = default_tab_stops(new_cols);
         self.rows = new_rows;
         self.cols = new_cols;
     }
 }
 
+/// Tab stops every 8 columns
+fn default_tab_stops(cols: usize) -> Vec<bool> {
+    (0..cols).map(|col| col > 0 && col % 8 == 0).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,6 +619,30 @@ mod tests {
         assert_eq!(c.to_hex(), "#ff8000");
     }
 
+    #[test]
+    fn test_color_from_xparse() {
+        let c = Color::new(255, 128, 0);
+        assert_eq!(Color::from_xparse("rgb:ff/80/00"), Some(c));
+        assert_eq!(Color::from_xparse("rgb:ffff/8080/0000"), Some(c));
+        assert_eq!(
+            Color::from_xparse("rgb:f/8/0"),
+            Some(Color::new(255, 136, 0))
+        );
+        assert_eq!(Color::from_xparse("#ff8000"), Some(c));
+        assert_eq!(Color::from_xparse("#f80"), Some(Color::new(240, 128, 0)));
+        assert_eq!(Color::from_xparse("#ffff80000000"), Some(c));
+        assert_eq!(Color::from_xparse("rgb:ff/80"), None);
+        assert_eq!(Color::from_xparse("#ff80"), None);
+        assert_eq!(Color::from_xparse("red"), None);
+    }
+
+    #[test]
+    fn test_color_to_xparse() {
+        let c = Color::new(255, 128, 0);
+        assert_eq!(c.to_xparse(), "rgb:ffff/8080/0000");
+        assert_eq!(Color::from_xparse(&c.to_xparse()), Some(c));
+    }
+
     #[test]
     fn test_style_default() {
         let s = Style::default();
@@ -333,6 +674,85 @@ mod tests {
         assert!(classes.contains("italic"));
     }
 
+    #[test]
+    fn test_hyperlink_from_osc8() {
+        assert_eq!(
+            Hyperlink::from_osc8(";https://example.com"),
+            Some(Hyperlink::new(None, "https://example.com"))
+        );
+        assert_eq!(
+            Hyperlink::from_osc8("foo=bar:id=err1;file:///src/main.rs"),
+            Some(Hyperlink::new(
+                Some("err1".to_string()),
+                "file:///src/main.rs"
+            ))
+        );
+        // URIs may themselves contain semicolons
+        assert_eq!(
+            Hyperlink::from_osc8(";https://example.com/a;b").map(|link| link.uri),
+            Some("https://example.com/a;b".to_string())
+        );
+        assert_eq!(Hyperlink::from_osc8(";"), None);
+        assert_eq!(Hyperlink::from_osc8(""), None);
+    }
+
+    #[test]
+    fn test_grid_wrapped() {
+        let mut grid = Grid::new(3, 4);
+        assert!(!grid.is_wrapped(0));
+        grid.set_wrapped(0, true);
+        assert!(grid.is_wrapped(0));
+        assert!(!grid.is_wrapped(5));
+        grid.clear();
+        assert!(!grid.is_wrapped(0));
+    }
+
+    #[test]
+    fn test_grid_marks() {
+        let mut grid = Grid::new(3, 4);
+        grid.add_mark(0, PromptMark::PromptStart);
+        grid.add_mark(0, PromptMark::CommandStart);
+        grid.add_mark(2, PromptMark::PromptStart);
+        grid.add_mark(9, PromptMark::PromptStart);
+        assert_eq!(
+            grid.marks(0),
+            [PromptMark::PromptStart, PromptMark::CommandStart]
+        );
+        assert!(grid.marks(9).is_empty());
+        assert_eq!(
+            grid.rows_with_mark(PromptMark::PromptStart)
+                .collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        grid.clear();
+        assert!(grid.marks(0).is_empty());
+    }
+
+    #[test]
+    fn test_cursor_style_default() {
+        let style = CursorStyle::default();
+        assert_eq!(style.shape, CursorShape::Block);
+        assert!(!style.blinking);
+    }
+
+    #[test]
+    fn test_cursor_style_from_decscusr() {
+        assert_eq!(CursorStyle::from_decscusr(0), None);
+        assert_eq!(
+            CursorStyle::from_decscusr(1),
+            Some(CursorStyle::new(CursorShape::Block, true))
+        );
+        assert_eq!(
+            CursorStyle::from_decscusr(4),
+            Some(CursorStyle::new(CursorShape::Underline, false))
+        );
+        assert_eq!(
+            CursorStyle::from_decscusr(5),
+            Some(CursorStyle::new(CursorShape::Bar, true))
+        );
+        assert_eq!(CursorShape::Bar.as_str(), "bar");
+    }
+
     #[test]
     fn test_grid_new() {
         let grid = Grid::new(24, 80);
@@ -397,6 +817,61 @@ mod tests {
         assert!(grid.get(15, 15).is_none());
     }
 
+    #[test]
+    fn test_grid_insert_cells() {
+        let mut grid = Grid::new(2, 4);
+        for (col, c) in "abcd".chars().enumerate() {
+            grid.set(0, col, Cell::new(c));
+        }
+        grid.insert_cells(0, 1, 2);
+        let row: String = grid
+            .iter_rows()
+            .next()
+            .unwrap()
+            .iter()
+            .map(|cell| cell.c)
+            .collect();
+        assert_eq!(row, "a  b");
+        // Out of range positions are ignored
+        grid.insert_cells(0, 4, 1);
+        grid.insert_cells(2, 0, 1);
+    }
+
+    #[test]
+    fn test_grid_line_sizes() {
+        let mut grid = Grid::new(3, 10);
+        assert_eq!(grid.line_size(0), LineSize::Single);
+        grid.set_line_size(1, LineSize::DoubleHeightTop);
+        grid.set_line_size(5, LineSize::DoubleWidth);
+        assert!(grid.line_size(1).is_double_width());
+        assert_eq!(grid.line_size(5), LineSize::Single);
+        grid.resize(2, 10);
+        assert_eq!(grid.line_size(1), LineSize::DoubleHeightTop);
+        grid.clear();
+        assert_eq!(grid.line_size(1), LineSize::Single);
+    }
+
+    #[test]
+    fn test_grid_tab_stops() {
+        let mut grid = Grid::new(2, 20);
+        assert_eq!(grid.next_tab_stop(0), 8);
+        assert_eq!(grid.next_tab_stop(8), 16);
+        assert_eq!(grid.next_tab_stop(16), 19);
+        assert_eq!(grid.prev_tab_stop(16), 8);
+        assert_eq!(grid.prev_tab_stop(3), 0);
+        grid.set_tab_stop(4, true);
+        grid.set_tab_stop(8, false);
+        assert_eq!(grid.next_tab_stop(0), 4);
+        assert_eq!(grid.next_tab_stop(4), 16);
+        grid.clear_tab_stops();
+        assert_eq!(grid.next_tab_stop(0), 19);
+        grid.set_tab_stop(3, true);
+        // Resizing restores the defaults
+        grid.resize(2, 30);
+        assert!(!grid.is_tab_stop(3));
+        assert_eq!(grid.next_tab_stop(16), 24);
+    }
+
     #[test]
     fn test_grid_iter_rows() {
         let grid = Grid::new(3, 4);
@@ -13,6 +13,8 @@ pub struct Theme {
     pub cursor: Option<Color>,
     /// Selection background color
     pub selection: Option<Color>,
+    /// ANSI colors 0-15 (defaults to the built-in palette)
+    pub palette: Option<[Color; 16]>,
 }
 
 impl Theme {
@@ -24,9 +26,17 @@ impl Theme {
             foreground,
             cursor: None,
             selection: None,
+            palette: None,
         }
     }
 
+    /// Use `palette` for ANSI colors 0-15
+    #[must_use]
+    pub const fn with_palette(mut self, palette: [Color; 16]) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
     /// Dark theme (default) - black background, light gray text
     #[must_use]
     pub const fn dark() -> Self {
@@ -139,5 +149,8 @@ mod tests {
         let theme = Theme::new(Color::new(10, 20, 30), Color::new(200, 210, 220));
         assert_eq!(theme.background.r, 10);
         assert_eq!(theme.foreground.r, 200);
+        assert_eq!(theme.palette, None);
+        let palette = [Color::new(1, 2, 3); 16];
+        assert_eq!(theme.with_palette(palette).palette, Some(palette));
     }
 }
@@ -1,16 +1,57 @@
 //! Dioxus terminal widget component
 
+use dioxus::html::geometry::{ClientPoint, PixelsRect};
 use dioxus::prelude::*;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::clipboard::{ClipboardPolicy, ClipboardRequest, DEFAULT_CLIPBOARD_LIMIT, osc52_reply};
+use crate::emulator::{TermEvent, TermState, process_byte};
+use crate::graphics::{DEFAULT_IMAGE_MEMORY_LIMIT, ImageView};
+use crate::handle::TerminalHandle;
+use crate::keyboard::{KeyInput, key_to_string};
+use crate::links::{DetectedLink, LinkDetector, LinkTarget};
+use crate::mouse::{MouseButton, MouseEventKind, MouseInput, cell_at, encode_mouse};
+use crate::notification::Notification;
 use crate::pty::Pty;
-use crate::term::{Cell, Color, Grid};
+use crate::term::{Color, CursorShape, CursorStyle, Grid, Hyperlink, LineSize};
 use crate::theme::Theme;
 
 /// Default monospace font stack
 pub const DEFAULT_FONT_FAMILY: &str =
     "JetBrains Mono, Menlo, Monaco, Consolas, ui-monospace, monospace";
 
+/// Coroutine ticks (~16ms each) between working directory polls
+const CWD_POLL_TICKS: u32 = 60;
+
+/// Longest a synchronized update (mode 2026) may hold back the screen
+const SYNC_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// URI schemes opened with the OS handler; others could launch arbitrary handlers
+const OPEN_SCHEMES: [&str; 4] = ["http", "https", "file", "mailto"];
+
+/// How long the visual bell inverts the terminal
+const VISUAL_BELL_DURATION: Duration = Duration::from_millis(100);
+
+/// Cursor shapes and blinking; colors come from per-cell CSS variables
+const CURSOR_CSS: &str = "
+.terminal-cursor { color: var(--cell-fg); background-color: var(--cell-bg); }
+.terminal-cursor-block { color: var(--cell-bg); background-color: var(--cursor-color); }
+.terminal-cursor-hollow { box-shadow: inset 0 0 0 1px var(--cursor-color); }
+.terminal-cursor-underline { box-shadow: inset 0 -2px 0 0 var(--cursor-color); }
+.terminal-cursor-bar { box-shadow: inset 2px 0 0 0 var(--cursor-color); }
+.terminal-cursor-blink { animation: terminal-cursor-blink 1s step-end infinite; }
+@keyframes terminal-cursor-blink {
+    50% { color: var(--cell-fg); background-color: var(--cell-bg); box-shadow: none; }
+}
+.terminal-link { cursor: pointer; }
+.terminal-link-hover { text-decoration: underline; }
+.terminal-bell-flash { filter: invert(1); }
+";
+
 /// Props for the Terminal component
 #[derive(Props, Clone, PartialEq)]
 pub struct TerminalProps {
@@ -26,6 +67,10 @@ pub struct TerminalProps {
     #[props(default)]
     pub shell: String,
 
+    /// Load the bundled shell integration into bash, zsh or fish (OSC 133 / OSC 7)
+    #[props(default)]
+    pub shell_integration: bool,
+
     /// Number of rows (default: 24)
     #[props(default = 24)]
     pub rows: u16,
@@ -54,6 +99,71 @@ pub struct TerminalProps {
     #[props(default)]
     pub foreground: Option<Color>,
 
+    /// Default cursor shape and blinking (applications may override it)
+    #[props(default)]
+    pub cursor_style: CursorStyle,
+
+    /// Called when the application sets the window title (OSC 0/2)
+    #[props(default)]
+    pub on_title_change: EventHandler<String>,
+
+    /// Also set the desktop window title when the application changes it
+    #[props(default)]
+    pub set_window_title: bool,
+
+    /// Called with the URI when a hyperlink is Ctrl+clicked (Cmd+click on macOS);
+    /// when unset `http`, `https`, `file` and `mailto` links are opened with the OS handler
+    #[props(default)]
+    pub on_link_click: Option<EventHandler<String>>,
+
+    /// Detector for URLs and `path:line:col` references in plain output (`None` disables it)
+    #[props(default = Some(LinkDetector::default()))]
+    pub link_detector: Option<LinkDetector>,
+
+    /// Called when a detected link is Ctrl+clicked (Cmd+click on macOS);
+    /// when unset URLs and paths are opened with the OS handler (relative paths
+    /// against the shell's working directory)
+    #[props(default)]
+    pub on_link_activate: Option<EventHandler<DetectedLink>>,
+
+    /// Called when the shell's working directory changes (OSC 7, or `/proc` polling)
+    #[props(default)]
+    pub on_cwd_change: EventHandler<PathBuf>,
+
+    /// Called once with a handle for querying the terminal
+    #[props(default)]
+    pub on_ready: EventHandler<TerminalHandle>,
+
+    /// Called when the application asks for a desktop notification (OSC 9 / OSC 777)
+    #[props(default)]
+    pub on_notification: EventHandler<Notification>,
+
+    /// Called when the application rings the bell (BEL), e.g. to request window
+    /// attention; repeated bells are rate limited
+    #[props(default)]
+    pub on_bell: EventHandler<()>,
+
+    /// Briefly flash the terminal when the bell rings
+    #[props(default)]
+    pub visual_bell: bool,
+
+    /// Also notify when a command that ran at least this long finishes while the
+    /// widget is unfocused (needs shell integration)
+    #[props(default)]
+    pub notify_command_after: Option<Duration>,
+
+    /// Which OSC 52 clipboard requests are allowed (default: copy only)
+    #[props(default)]
+    pub clipboard_policy: ClipboardPolicy,
+
+    /// Largest text copied or read through OSC 52, in bytes (default: 1 MiB)
+    #[props(default = DEFAULT_CLIPBOARD_LIMIT)]
+    pub clipboard_limit: usize,
+
+    /// Memory kept for inline images (decoded pixels and data URLs), in bytes (default: 64 MiB)
+    #[props(default = DEFAULT_IMAGE_MEMORY_LIMIT)]
+    pub image_memory_limit: usize,
+
     /// CSS class for the container
     #[props(default)]
     pub class: String,
@@ -63,35 +173,13 @@ fn default_shell() -> String {
     std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
 }
 
-/// Escape sequence parsing state
-#[derive(Default)]
-enum EscapeState {
-    #[default]
-    Normal,
-    Escape,    // Just saw ESC
-    Csi,       // In CSI sequence (ESC [)
-    Osc,       // In OSC sequence (ESC ]) - consume until BEL or ST
-    OscEscape, // In OSC, just saw ESC (looking for \)
-    Dcs,       // In DCS sequence (ESC P) - consume until ST
-    DcsEscape, // In DCS, just saw ESC (looking for \)
-}
-
-/// Terminal state shared between render and coroutine
-#[allow(clippy::struct_excessive_bools)]
-struct TermState {
-    pty: Option<Pty>,
-    cursor_row: usize,
-    cursor_col: usize,
-    // Current text attributes
-    fg: Color,
-    bg: Color,
-    bold: bool,
-    dim: bool,
-    italic: bool,
-    underline: bool,
-    // Escape sequence parsing
-    escape_state: EscapeState,
-    escape_buf: Vec<u8>,
+/// Cursor state published by the coroutine for rendering
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CursorView {
+    row: usize,
+    col: usize,
+    visible: bool,
+    style: CursorStyle,
 }
 
 /// Terminal emulator widget for Dioxus
@@ -101,11 +189,39 @@ pub fn Terminal(props: TerminalProps) -> Element {
     let cols = props.cols as usize;
 
     // Resolve colors: explicit props override theme
-    let bg_color = props.background.unwrap_or(props.theme.background);
-    let fg_color = props.foreground.unwrap_or(props.theme.foreground);
+    let theme_bg = props.background.unwrap_or(props.theme.background);
+    let theme_fg = props.foreground.unwrap_or(props.theme.foreground);
+    let theme_cursor = props.theme.cursor.unwrap_or(theme_fg);
+    let default_cursor = props.cursor_style;
 
     let mut grid = use_signal(|| Grid::new(rows, cols));
-    let mut cursor_pos = use_signal(|| (0usize, 0usize)); // (row, col)
+    let mut cursor = use_signal(|| CursorView {
+        row: 0,
+        col: 0,
+        visible: true,
+        style: default_cursor,
+    });
+    // Foreground, background and cursor colors set by the application (OSC 10/11/12)
+    let mut color_overrides = use_signal(|| [None::<Color>; 3]);
+    // Reverse video (DECSCNM) swaps the default foreground and background
+    let mut reverse_video = use_signal(|| false);
+    // Whether the application has asked for mouse reports
+    let mut mouse_reporting = use_signal(|| false);
+    // Whether the widget has keyboard focus (unfocused cursor is drawn hollow)
+    let mut focused = use_signal(|| false);
+    // While Shift is held, mouse events are left to local selection
+    let mut shift_held = use_signal(|| false);
+    // Grid element and its bounding box, used to map pointer positions to cells
+    let mut grid_element = use_signal(|| None::<Rc<MountedData>>);
+    let mut grid_rect = use_signal(|| None::<PixelsRect>);
+    // Hyperlink under the pointer, underlined across all of its cells
+    let mut hovered_link = use_signal(|| None::<Arc<Hyperlink>>);
+    // Detected link under the pointer while Ctrl (Cmd) is held
+    let mut hovered_detected = use_signal(|| None::<DetectedLink>);
+    // Inline images on screen (Sixel, kitty graphics, iTerm2)
+    let mut images = use_signal(Vec::<ImageView>::new);
+    // Visual bell flash in progress
+    let mut bell_flash = use_signal(|| false);
 
     // Shared state for PTY and cursor
     let state = use_hook(|| {
@@ -120,28 +236,66 @@ pub fn Terminal(props: TerminalProps) -> Element {
         };
 
         let args_refs: Vec<&str> = args.iter().map(String::as_str).collect();
-        let pty = Pty::spawn(&command, &args_refs, props.rows, props.cols).ok();
-
-        Arc::new(Mutex::new(TermState {
-            pty,
-            cursor_row: 0,
-            cursor_col: 0,
-            fg: Color::default_fg(),
-            bg: Color::default_bg(),
-            bold: false,
-            dim: false,
-            italic: false,
-            underline: false,
-            escape_state: EscapeState::Normal,
-            escape_buf: Vec::new(),
-        }))
+        let pty = if props.shell_integration {
+            Pty::spawn_with_shell_integration(&command, &args_refs, props.rows, props.cols)
+        } else {
+            Pty::spawn(&command, &args_refs, props.rows, props.cols)
+        }
+        .ok();
+
+        let mut term = TermState::new(pty);
+        term.clipboard_limit = props.clipboard_limit;
+        term.images.limit = props.image_memory_limit;
+        Arc::new(Mutex::new(term))
     });
 
+    // Color queries are answered from the theme unless the application changed them
+    if let Ok(mut s) = state.lock() {
+        s.theme_colors = [theme_fg, theme_bg, theme_cursor];
+        s.set_theme_palette(props.theme.palette);
+    }
+    let overrides = *color_overrides.read();
+    let mut fg_color = overrides[0].unwrap_or(theme_fg);
+    let mut bg_color = overrides[1].unwrap_or(theme_bg);
+    if reverse_video() {
+        std::mem::swap(&mut fg_color, &mut bg_color);
+    }
+    let cursor_color = overrides[2].unwrap_or(theme_cursor);
+
     // Coroutine to read PTY output
+    let on_title_change = props.on_title_change;
+    let set_window_title = props.set_window_title;
+    let clipboard_policy = props.clipboard_policy;
+    let clipboard_limit = props.clipboard_limit;
+    let on_cwd_change = props.on_cwd_change;
+    let on_ready = props.on_ready;
+    let on_notification = props.on_notification;
+    let notify_command_after = props.notify_command_after;
+    let on_bell = props.on_bell;
+    let visual_bell = props.visual_bell;
+    // Command line named in the notification when a command without shell integration exits
+    let command_line = if props.shell.is_empty() {
+        std::iter::once(&props.command)
+            .chain(&props.args)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        props.shell.clone()
+    };
     let state_clone = state.clone();
     use_coroutine(move |_rx: UnboundedReceiver<()>| {
         let state = state_clone.clone();
+        let command_line = command_line.clone();
         async move {
+            on_ready.call(TerminalHandle::new(state.clone(), grid));
+            let mut ticks: u32 = 0;
+            // Screen the emulator writes to, copied to `grid` when published
+            let mut screen = grid.peek().clone();
+            let mut dirty = false;
+            let mut sync_since = None::<Instant>;
+            // Whether the child's exit has been handled
+            let mut exited = false;
             loop {
                 // Try to read from PTY
                 let data = {
@@ -153,15 +307,137 @@ pub fn Terminal(props: TerminalProps) -> Element {
                     }
                 };
 
+                let mut events = Vec::new();
+                if data.is_none() && !exited {
+                    // A command still running when the shell exits won't report `D`
+                    let mut s = state.lock().unwrap();
+                    if let Some(pty) = s.pty.as_mut().filter(|pty| pty.has_exited()) {
+                        let (exit_code, run_time) = (pty.exit_code(), pty.elapsed());
+                        exited = true;
+                        s.child_exited(&command_line, exit_code, run_time);
+                        events = s.take_events();
+                    }
+                }
                 if let Some(bytes) = data {
                     // Process output bytes
                     let mut s = state.lock().unwrap();
                     for byte in bytes {
-                        process_byte(&mut s, &mut grid, byte, rows, cols);
+                        process_byte(&mut s, &mut screen, byte, rows, cols);
+                    }
+                    // Send query responses back to the application
+                    let replies = s.take_replies();
+                    if !replies.is_empty() {
+                        if let Some(ref pty) = s.pty {
+                            let _ = pty.write(&replies);
+                        }
                     }
-                    // Update cursor position signal
-                    cursor_pos.set((s.cursor_row, s.cursor_col));
+                    events = s.take_events();
+                    dirty = true;
+                    // Note when a synchronized update (mode 2026) starts
+                    if !s.modes.synchronized {
+                        sync_since = None;
+                    } else if sync_since.is_none() {
+                        sync_since = Some(Instant::now());
+                    }
+                }
+
+                // Publish the screen, unless the application is still drawing a frame
+                let holding = sync_since.is_some_and(|since| since.elapsed() < SYNC_TIMEOUT);
+                if dirty && !holding {
+                    dirty = false;
+                    grid.set(screen.clone());
+                    let mut s = state.lock().unwrap();
+                    s.published_lines = s.scrolled_lines;
+                    let view = CursorView {
+                        row: s.cursor_row,
+                        col: s.cursor_col,
+                        visible: s.cursor_visible,
+                        style: s.cursor_style(default_cursor),
+                    };
+                    if *cursor.peek() != view {
+                        cursor.set(view);
+                    }
+                    let reporting = s.mouse.is_active();
+                    let overrides = s.color_overrides;
+                    let reverse = s.modes.reverse_video;
+                    let visible = s.images.visible(s.scrolled_lines, rows);
                     drop(s);
+                    if *mouse_reporting.peek() != reporting {
+                        mouse_reporting.set(reporting);
+                    }
+                    if *color_overrides.peek() != overrides {
+                        color_overrides.set(overrides);
+                    }
+                    if *reverse_video.peek() != reverse {
+                        reverse_video.set(reverse);
+                    }
+                    if *images.peek() != visible {
+                        images.set(visible);
+                    }
+                }
+
+                // Without OSC 7 reports, follow the foreground process's directory
+                ticks = ticks.wrapping_add(1);
+                if ticks % CWD_POLL_TICKS == 0 {
+                    let mut s = state.lock().unwrap();
+                    if !s.cwd_reported {
+                        if let Some(cwd) = s.pty.as_ref().and_then(Pty::cwd) {
+                            s.set_cwd(cwd);
+                            events.extend(s.take_events());
+                        }
+                    }
+                }
+
+                for event in events {
+                    match event {
+                        TermEvent::TitleChanged(title) => {
+                            if set_window_title {
+                                document::document().set_title(title.clone());
+                            }
+                            on_title_change.call(title);
+                        }
+                        TermEvent::ClipboardWrite(text) => {
+                            if clipboard_policy.allows(&ClipboardRequest::Write(text.clone())) {
+                                write_clipboard(text);
+                            }
+                        }
+                        TermEvent::ClipboardRead { selection } => {
+                            if clipboard_policy.allows(&ClipboardRequest::Read) {
+                                let state = state.clone();
+                                spawn(async move {
+                                    let Some(text) = read_clipboard().await else {
+                                        return;
+                                    };
+                                    let reply = osc52_reply(&selection, &text, clipboard_limit);
+                                    if let Ok(s) = state.lock() {
+                                        if let Some(ref pty) = s.pty {
+                                            let _ = pty.write(&reply);
+                                        }
+                                    }
+                                });
+                            }
+                        }
+                        TermEvent::CwdChanged(cwd) => on_cwd_change.call(cwd),
+                        TermEvent::Notification(notification) => on_notification.call(notification),
+                        TermEvent::Bell => {
+                            on_bell.call(());
+                            if visual_bell {
+                                bell_flash.set(true);
+                                spawn(async move {
+                                    tokio::time::sleep(VISUAL_BELL_DURATION).await;
+                                    bell_flash.set(false);
+                                });
+                            }
+                        }
+                        TermEvent::CommandFinished(block) => {
+                            let long = notify_command_after.is_some_and(|after| {
+                                block.duration.is_some_and(|duration| duration >= after)
+                            });
+                            if long && !*focused.peek() {
+                                on_notification.call(Notification::command_finished(&block));
+                            }
+                        }
+                    }
                 }
 
                 // Small delay to avoid busy loop
@@ -170,17 +446,201 @@ pub fn Terminal(props: TerminalProps) -> Element {
         }
     });
 
-    // Handle keyboard input
+    // Handle keyboard input (key up is only reported by the kitty protocol)
     let state_for_key = state.clone();
     let onkeydown = move |evt: KeyboardEvent| {
-        let key_str = key_to_string(&evt);
-        if !key_str.is_empty() {
-            if let Ok(s) = state_for_key.lock() {
-                if let Some(ref pty) = s.pty {
-                    let _ = pty.write(key_str.as_bytes());
+        if evt.key() == Key::Shift {
+            shift_held.set(true);
+        }
+        send_key(&state_for_key, &evt, false);
+    };
+    let state_for_key = state.clone();
+    let onkeyup = move |evt: KeyboardEvent| {
+        match evt.key() {
+            Key::Shift => shift_held.set(false),
+            Key::Control | Key::Meta if hovered_detected.peek().is_some() => {
+                hovered_detected.set(None);
+            }
+            _ => {}
+        }
+        send_key(&state_for_key, &evt, true);
+    };
+
+    // Report focus changes to applications that asked for them (mode 1004)
+    let state_for_focus = state.clone();
+    let onfocus = move |_: FocusEvent| {
+        focused.set(true);
+        send_focus(&state_for_focus, true);
+    };
+    let state_for_focus = state.clone();
+    let onblur = move |_: FocusEvent| {
+        focused.set(false);
+        shift_held.set(false);
+        send_focus(&state_for_focus, false);
+    };
+
+    // Re-measure the grid (layout may have changed since it was mounted)
+    let refresh_grid_rect = move || {
+        if let Some(element) = grid_element() {
+            spawn(async move {
+                if let Ok(rect) = element.get_client_rect().await {
+                    grid_rect.set(Some(rect));
                 }
+            });
+        }
+    };
+
+    // Images cover whole cells, so the emulator needs the measured cell size
+    let state_for_rect = state.clone();
+    use_effect(move || {
+        if let Some(rect) = grid_rect() {
+            if let Ok(mut s) = state_for_rect.lock() {
+                s.cell_size = cell_size(rect, rows, cols);
             }
         }
+    });
+
+    // Handle mouse input (reported to the application when it enables tracking)
+    let state_for_mouse = state.clone();
+    let onmousedown = move |evt: MouseEvent| {
+        if let Some(button) = evt.trigger_button().and_then(MouseButton::from_dioxus) {
+            let kind = MouseEventKind::Press(button);
+            let point = evt.client_coordinates();
+            send_mouse(
+                &state_for_mouse,
+                kind,
+                point,
+                evt.modifiers(),
+                grid_rect(),
+                rows,
+                cols,
+            );
+        }
+    };
+    let state_for_mouse = state.clone();
+    let onmouseup = move |evt: MouseEvent| {
+        if let Some(button) = evt.trigger_button().and_then(MouseButton::from_dioxus) {
+            let kind = MouseEventKind::Release(button);
+            let point = evt.client_coordinates();
+            send_mouse(
+                &state_for_mouse,
+                kind,
+                point,
+                evt.modifiers(),
+                grid_rect(),
+                rows,
+                cols,
+            );
+        }
+    };
+    let state_for_mouse = state.clone();
+    let detector = props.link_detector.clone();
+    let onmousemove = move |evt: MouseEvent| {
+        let point = evt.client_coordinates();
+        let link = link_at(&grid.read(), point, grid_rect(), rows, cols);
+        // Plain-text links are only detected while Ctrl (Cmd) is held
+        let modifiers = evt.modifiers();
+        let detected = if link.is_none() && (modifiers.ctrl() || modifiers.meta()) {
+            detected_link_at(
+                detector.as_ref(),
+                &grid.read(),
+                point,
+                grid_rect(),
+                rows,
+                cols,
+            )
+        } else {
+            None
+        };
+        if *hovered_link.peek() != link {
+            hovered_link.set(link);
+        }
+        if *hovered_detected.peek() != detected {
+            hovered_detected.set(detected);
+        }
+        let held = evt.held_buttons().iter().find_map(MouseButton::from_dioxus);
+        let kind = MouseEventKind::Motion(held);
+        send_mouse(
+            &state_for_mouse,
+            kind,
+            point,
+            evt.modifiers(),
+            grid_rect(),
+            rows,
+            cols,
+        );
+    };
+    let state_for_mouse = state.clone();
+    let onwheel = move |evt: WheelEvent| {
+        let delta = evt.delta().strip_units();
+        let button = if delta.y < 0.0 {
+            MouseButton::WheelUp
+        } else if delta.y > 0.0 {
+            MouseButton::WheelDown
+        } else if delta.x < 0.0 {
+            MouseButton::WheelLeft
+        } else if delta.x > 0.0 {
+            MouseButton::WheelRight
+        } else {
+            return;
+        };
+        let kind = MouseEventKind::Press(button);
+        let point = evt.client_coordinates();
+        if send_mouse(
+            &state_for_mouse,
+            kind,
+            point,
+            evt.modifiers(),
+            grid_rect(),
+            rows,
+            cols,
+        ) {
+            evt.prevent_default();
+        }
+    };
+    // Ctrl+click (Cmd+click on macOS) follows hyperlinks
+    let on_link_click = props.on_link_click;
+    let on_link_activate = props.on_link_activate;
+    let detector = props.link_detector.clone();
+    let state_for_click = state.clone();
+    let onclick = move |evt: MouseEvent| {
+        let modifiers = evt.modifiers();
+        if !(modifiers.ctrl() || modifiers.meta()) {
+            return;
+        }
+        let point = evt.client_coordinates();
+        if let Some(link) = link_at(&grid.read(), point, grid_rect(), rows, cols) {
+            match on_link_click {
+                Some(handler) => handler.call(link.uri.clone()),
+                None => open_link(&link.uri),
+            }
+        } else if let Some(detected) = detected_link_at(
+            detector.as_ref(),
+            &grid.read(),
+            point,
+            grid_rect(),
+            rows,
+            cols,
+        ) {
+            match (on_link_activate, &detected.target) {
+                (Some(handler), _) => handler.call(detected),
+                (None, LinkTarget::Url(url)) => open_link(url),
+                (None, LinkTarget::Path { path, .. }) => {
+                    // Relative paths are relative to the shell, not to this process
+                    let cwd = TerminalHandle::new(state_for_click.clone(), grid).cwd();
+                    let home = std::env::var_os("HOME").map(PathBuf::from);
+                    if let Some(path) = resolve_path(path, cwd.as_deref(), home.as_deref()) {
+                        open_with_os(path.as_os_str());
+                    }
+                }
+            }
+        }
+    };
+    let oncontextmenu = move |evt: MouseEvent| {
+        // Right clicks belong to the application while it tracks the mouse
+        if mouse_reporting() && !evt.modifiers().shift() {
+            evt.prevent_default();
+        }
     };
 
     let container_style = format!(
@@ -191,355 +651,330 @@ pub fn Terminal(props: TerminalProps) -> Element {
         props.font_size
     );
 
+    // Suppress local text selection while the application owns the mouse
+    let select_class = if mouse_reporting() && !shift_held() {
+        "select-none"
+    } else {
+        ""
+    };
+    let bell_class = if bell_flash() {
+        "terminal-bell-flash"
+    } else {
+        ""
+    };
     let container_class = format!(
-        "terminal-container overflow-hidden select-none {}",
+        "terminal-container overflow-hidden {select_class} {bell_class} {}",
         props.class
     );
 
+    let cursor_view = *cursor.read();
+    let line_styles: Vec<&str> = (0..rows)
+        .map(|row| line_style(grid.read().line_size(row)))
+        .collect();
+    let is_focused = focused();
+    let hovered = hovered_link.read().clone();
+    let detected = hovered_detected.read().clone();
+
     rsx! {
+        style { {CURSOR_CSS} }
         div {
             class: "{container_class}",
             style: "{container_style}",
             tabindex: "0",
             onkeydown: onkeydown,
+            onkeyup: onkeyup,
+            onfocus: onfocus,
+            onblur: onblur,
+            onmousedown: onmousedown,
+            onmouseup: onmouseup,
+            onmousemove: onmousemove,
+            onwheel: onwheel,
+            onclick: onclick,
+            oncontextmenu: oncontextmenu,
+            onmouseenter: move |_| refresh_grid_rect(),
+            onmouseleave: move |_| {
+                hovered_link.set(None);
+                hovered_detected.set(None);
+            },
 
-            // Render grid
-            div { class: "terminal-grid whitespace-pre font-mono",
+            // Render grid (inline-block so its box matches the cells exactly)
+            div {
+                class: "terminal-grid whitespace-pre font-mono",
+                style: "display: inline-block; position: relative; overflow: hidden; isolation: isolate;",
+                onmounted: move |evt: MountedEvent| {
+                    grid_element.set(Some(evt.data()));
+                    refresh_grid_rect();
+                },
                 for (row_idx, row) in grid.read().iter_rows().enumerate() {
-                    div { class: "terminal-row", key: "{row_idx}",
+                    div {
+                        class: "terminal-row",
+                        key: "{row_idx}",
+                        style: "{line_styles[row_idx]}",
                         for (col_idx, cell) in row.iter().enumerate() {
                             {
-                                let (cursor_row, cursor_col) = *cursor_pos.read();
-                                let is_cursor = row_idx == cursor_row && col_idx == cursor_col;
-                                // Use theme background for cells with default black bg
+                                let is_cursor = cursor_view.visible
+                                    && row_idx == cursor_view.row
+                                    && col_idx == cursor_view.col;
+                                // Use theme colors for cells with default colors
                                 let cell_bg = if cell.bg == Color::default_bg() {
                                     bg_color
                                 } else {
                                     cell.bg
                                 };
-                                // Invert colors for cursor (block cursor style)
-                                let (fg, bg) = if is_cursor {
-                                    (cell_bg.to_css(), fg_color.to_css())
+                                let cell_fg = if cell.fg == Color::default_fg() {
+                                    fg_color
+                                } else {
+                                    cell.fg
+                                };
+                                let fg = cell_fg.to_css();
+                                let bg = cell_bg.to_css();
+                                // Default backgrounds show the container, and images below text
+                                let span_bg = if cell.bg == Color::default_bg() {
+                                    "transparent".to_string()
                                 } else {
-                                    (cell.fg.to_css(), cell_bg.to_css())
+                                    bg.clone()
                                 };
-                                rsx! {
-                                    span {
-                                        key: "{col_idx}",
-                                        class: "{cell.style.to_css_classes()}",
-                                        style: "color: {fg}; background-color: {bg};",
-                                        "{cell.c}"
+                                let in_detected = detected
+                                    .as_ref()
+                                    .is_some_and(|link| link.contains(row_idx, col_idx));
+                                let link_class = match &cell.link {
+                                    Some(link) if hovered.as_deref() == Some(link) => {
+                                        " terminal-link terminal-link-hover"
+                                    }
+                                    Some(_) => " terminal-link",
+                                    None if in_detected => " terminal-link terminal-link-hover",
+                                    None => "",
+                                };
+                                if is_cursor {
+                                    // Block cursor is drawn hollow when unfocused; blinking
+                                    // stops while unfocused so the cursor stays findable
+                                    let shape = match cursor_view.style.shape {
+                                        CursorShape::Block if !is_focused => "hollow",
+                                        shape => shape.as_str(),
+                                    };
+                                    let blink = if cursor_view.style.blinking && is_focused {
+                                        " terminal-cursor-blink"
+                                    } else {
+                                        ""
+                                    };
+                                    rsx! {
+                                        span {
+                                            key: "{col_idx}",
+                                            class: "{cell.style.to_css_classes()} terminal-cursor terminal-cursor-{shape}{blink}{link_class}",
+                                            style: "--cell-fg: {fg}; --cell-bg: {bg}; --cursor-color: {cursor_color.to_css()};",
+                                            "{cell.c}"
+                                        }
+                                    }
+                                } else {
+                                    rsx! {
+                                        span {
+                                            key: "{col_idx}",
+                                            class: "{cell.style.to_css_classes()}{link_class}",
+                                            style: "color: {fg}; background-color: {span_bg};",
+                                            "{cell.c}"
+                                        }
                                     }
                                 }
                             }
                         }
                     }
                 }
+                // Inline images over the cells they cover; negative z-index goes below text
+                for image in images.read().iter() {
+                    img {
+                        key: "img-{image.id}",
+                        src: "{image.url}",
+                        style: "position: absolute; top: calc({image.row} * 1.2em + {image.offset.1}px); left: calc({image.col}ch + {image.offset.0}px); width: {image.width}px; height: {image.height}px; z-index: {image.z}; pointer-events: none;",
+                    }
+                }
             }
         }
     }
 }
 
-/// Process a single byte of terminal output
-fn process_byte(
-    state: &mut TermState,
-    grid: &mut Signal<Grid>,
-    byte: u8,
-    rows: usize,
-    cols: usize,
-) {
-    match state.escape_state {
-        EscapeState::Normal => match byte {
-            // Escape - start escape sequence
-            0x1b => {
-                state.escape_state = EscapeState::Escape;
-                state.escape_buf.clear();
-            }
-            // Newline
-            b'\n' => {
-                state.cursor_row += 1;
-                if state.cursor_row >= rows {
-                    scroll_up(grid, rows, cols);
-                    state.cursor_row = rows - 1;
-                }
-            }
-            // Carriage return
-            b'\r' => {
-                state.cursor_col = 0;
-            }
-            // Backspace
-            0x08 => {
-                if state.cursor_col > 0 {
-                    state.cursor_col -= 1;
-                }
-            }
-            // Tab
-            b'\t' => {
-                let next_tab = (state.cursor_col / 8 + 1) * 8;
-                state.cursor_col = next_tab.min(cols - 1);
-            }
-            // Printable characters
-            0x20..=0x7e | 0x80..=0xff => {
-                let c = byte as char;
-                let cell = Cell {
-                    c,
-                    fg: state.fg,
-                    bg: state.bg,
-                    style: crate::term::Style {
-                        bold: state.bold,
-                        dim: state.dim,
-                        italic: state.italic,
-                        underline: state.underline,
-                        strikethrough: false,
-                        inverse: false,
-                    },
-                };
-                grid.write().set(state.cursor_row, state.cursor_col, cell);
-                state.cursor_col += 1;
-                if state.cursor_col >= cols {
-                    state.cursor_col = 0;
-                    state.cursor_row += 1;
-                    if state.cursor_row >= rows {
-                        scroll_up(grid, rows, cols);
-                        state.cursor_row = rows - 1;
-                    }
-                }
-            }
-            // Other control characters - ignore
-            _ => {}
-        },
-        EscapeState::Escape => {
-            match byte {
-                b'[' => state.escape_state = EscapeState::Csi,
-                b']' => state.escape_state = EscapeState::Osc,
-                b'P' => state.escape_state = EscapeState::Dcs,
-                // Single-character sequences - just ignore and return to normal
-                // ESC 7 (save cursor), ESC 8 (restore cursor), ESC c (reset), etc.
-                _ => state.escape_state = EscapeState::Normal,
-            }
-        }
-        EscapeState::Osc => {
-            // OSC sequences end with BEL (0x07) or ST (ESC \)
-            match byte {
-                0x07 => state.escape_state = EscapeState::Normal,
-                0x1b => state.escape_state = EscapeState::OscEscape,
-                _ => {} // Consume all other bytes
-            }
+/// CSS scaling for a row's line size (DECDWL/DECDHL); the grid clips the overflow
+fn line_style(size: LineSize) -> &'static str {
+    match size {
+        LineSize::Single => "",
+        LineSize::DoubleWidth => "transform: scaleX(2); transform-origin: left;",
+        // Each half of a double-height line shows its half of the glyphs
+        LineSize::DoubleHeightTop => {
+            "transform: scale(2); transform-origin: left top; clip-path: inset(0 0 50% 0);"
         }
-        EscapeState::OscEscape => {
-            if byte == b'\\' {
-                state.escape_state = EscapeState::Normal;
-            } else {
-                state.escape_state = EscapeState::Osc;
-            }
+        LineSize::DoubleHeightBottom => {
+            "transform: scale(2); transform-origin: left bottom; clip-path: inset(50% 0 0 0);"
         }
-        EscapeState::Dcs => {
-            // DCS sequences end with ST (ESC \)
-            if byte == 0x1b {
-                state.escape_state = EscapeState::DcsEscape;
-            }
-            // Consume all other bytes
+    }
+}
+
+/// Size of a cell in whole pixels, from the grid's bounding box
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn cell_size(rect: PixelsRect, rows: usize, cols: usize) -> (u32, u32) {
+    let width = (rect.width() / cols as f64).round().max(1.0);
+    let height = (rect.height() / rows as f64).round().max(1.0);
+    (width as u32, height as u32)
+}
+
+/// Encode a keyboard event for the current keyboard modes and write it to the PTY
+fn send_key(state: &Arc<Mutex<TermState>>, evt: &KeyboardEvent, released: bool) {
+    if let Ok(s) = state.lock() {
+        let mut key_str = key_to_string(&KeyInput::from_event(evt, released), &s.keyboard);
+        // In newline mode (LNM) Enter sends CR LF
+        if s.modes.newline && key_str == "\r" {
+            key_str.push('\n');
         }
-        EscapeState::DcsEscape => {
-            if byte == b'\\' {
-                state.escape_state = EscapeState::Normal;
-            } else {
-                state.escape_state = EscapeState::Dcs;
+        if !key_str.is_empty() {
+            if let Some(ref pty) = s.pty {
+                let _ = pty.write(key_str.as_bytes());
             }
         }
-        EscapeState::Csi => {
-            if byte.is_ascii_alphabetic() {
-                // End of CSI sequence
-                if byte == b'm' {
-                    // SGR - Select Graphic Rendition
-                    process_sgr(state);
-                }
-                // Other CSI sequences (cursor movement, etc.) - ignore for now
-                state.escape_state = EscapeState::Normal;
-                state.escape_buf.clear();
-            } else {
-                // Buffer the parameter bytes
-                state.escape_buf.push(byte);
+    }
+}
+
+/// Send `CSI I` / `CSI O` if the application enabled focus reporting
+fn send_focus(state: &Arc<Mutex<TermState>>, focused: bool) {
+    if let Ok(s) = state.lock() {
+        if s.focus_reporting {
+            if let Some(ref pty) = s.pty {
+                let _ = pty.write(if focused { b"\x1b[I" } else { b"\x1b[O" });
             }
         }
     }
 }
 
-/// Process SGR (Select Graphic Rendition) escape sequence
-fn process_sgr(state: &mut TermState) {
-    let params_str = String::from_utf8_lossy(&state.escape_buf);
-    let params: Vec<u8> = if params_str.is_empty() {
-        vec![0] // Default to reset
-    } else {
-        params_str
-            .split(';')
-            .filter_map(|s| s.parse().ok())
-            .collect()
+/// Report a mouse event to the application, returning whether it was sent
+///
+/// Events with Shift held are never reported, so local selection still works.
+fn send_mouse(
+    state: &Arc<Mutex<TermState>>,
+    kind: MouseEventKind,
+    point: ClientPoint,
+    modifiers: Modifiers,
+    rect: Option<PixelsRect>,
+    rows: usize,
+    cols: usize,
+) -> bool {
+    let Some(rect) = rect else {
+        return false;
     };
-
-    let mut i = 0;
-    while i < params.len() {
-        match params[i] {
-            0 => {
-                // Reset all attributes
-                state.fg = Color::default_fg();
-                state.bg = Color::default_bg();
-                state.bold = false;
-                state.dim = false;
-                state.italic = false;
-                state.underline = false;
-            }
-            1 => state.bold = true,
-            2 => state.dim = true,
-            3 => state.italic = true,
-            4 => state.underline = true,
-            22 => {
-                state.bold = false;
-                state.dim = false;
-            }
-            23 => state.italic = false,
-            24 => state.underline = false,
-            // Standard foreground colors (30-37)
-            30 => state.fg = Color::new(0, 0, 0),       // Black
-            31 => state.fg = Color::new(205, 49, 49),   // Red
-            32 => state.fg = Color::new(13, 188, 121),  // Green
-            33 => state.fg = Color::new(229, 229, 16),  // Yellow
-            34 => state.fg = Color::new(36, 114, 200),  // Blue
-            35 => state.fg = Color::new(188, 63, 188),  // Magenta
-            36 => state.fg = Color::new(17, 168, 205),  // Cyan
-            37 => state.fg = Color::new(229, 229, 229), // White
-            39 => state.fg = Color::default_fg(),       // Default fg
-            // Standard background colors (40-47)
-            40 => state.bg = Color::new(0, 0, 0),       // Black
-            41 => state.bg = Color::new(205, 49, 49),   // Red
-            42 => state.bg = Color::new(13, 188, 121),  // Green
-            43 => state.bg = Color::new(229, 229, 16),  // Yellow
-            44 => state.bg = Color::new(36, 114, 200),  // Blue
-            45 => state.bg = Color::new(188, 63, 188),  // Magenta
-            46 => state.bg = Color::new(17, 168, 205),  // Cyan
-            47 => state.bg = Color::new(229, 229, 229), // White
-            49 => state.bg = Color::default_bg(),       // Default bg
-            // Bright foreground colors (90-97)
-            90 => state.fg = Color::new(102, 102, 102), // Bright black
-            91 => state.fg = Color::new(241, 76, 76),   // Bright red
-            92 => state.fg = Color::new(35, 209, 139),  // Bright green
-            93 => state.fg = Color::new(245, 245, 67),  // Bright yellow
-            94 => state.fg = Color::new(59, 142, 234),  // Bright blue
-            95 => state.fg = Color::new(214, 112, 214), // Bright magenta
-            96 => state.fg = Color::new(41, 184, 219),  // Bright cyan
-            97 => state.fg = Color::new(255, 255, 255), // Bright white
-            // Bright background colors (100-107)
-            100 => state.bg = Color::new(102, 102, 102),
-            101 => state.bg = Color::new(241, 76, 76),
-            102 => state.bg = Color::new(35, 209, 139),
-            103 => state.bg = Color::new(245, 245, 67),
-            104 => state.bg = Color::new(59, 142, 234),
-            105 => state.bg = Color::new(214, 112, 214),
-            106 => state.bg = Color::new(41, 184, 219),
-            107 => state.bg = Color::new(255, 255, 255),
-            // 256-color mode (38;5;N or 48;5;N)
-            38 => {
-                if i + 2 < params.len() && params[i + 1] == 5 {
-                    state.fg = color_from_256(params[i + 2]);
-                    i += 2;
-                }
-            }
-            48 => {
-                if i + 2 < params.len() && params[i + 1] == 5 {
-                    state.bg = color_from_256(params[i + 2]);
-                    i += 2;
-                }
-            }
-            _ => {}
-        }
-        i += 1;
+    if modifiers.shift() {
+        return false;
+    }
+    let Ok(mut s) = state.lock() else {
+        return false;
+    };
+    if !s.mouse.is_active() {
+        return false;
     }
+    let (row, col) = cell_at(point, rect, rows, cols);
+    // Only report motion when the pointer enters a new cell
+    if matches!(kind, MouseEventKind::Motion(_)) && s.mouse_cell == Some((row, col)) {
+        return false;
+    }
+    s.mouse_cell = Some((row, col));
+    let input = MouseInput {
+        kind,
+        row,
+        col,
+        modifiers,
+    };
+    let Some(report) = encode_mouse(&input, s.mouse) else {
+        return false;
+    };
+    if let Some(ref pty) = s.pty {
+        let _ = pty.write(&report);
+    }
+    true
 }
 
-/// Convert 256-color palette index to RGB
-fn color_from_256(n: u8) -> Color {
-    match n {
-        // Standard colors (0-15)
-        0 => Color::new(0, 0, 0),
-        1 => Color::new(205, 49, 49),
-        2 => Color::new(13, 188, 121),
-        3 => Color::new(229, 229, 16),
-        4 => Color::new(36, 114, 200),
-        5 => Color::new(188, 63, 188),
-        6 => Color::new(17, 168, 205),
-        7 => Color::new(229, 229, 229),
-        8 => Color::new(102, 102, 102),
-        9 => Color::new(241, 76, 76),
-        10 => Color::new(35, 209, 139),
-        11 => Color::new(245, 245, 67),
-        12 => Color::new(59, 142, 234),
-        13 => Color::new(214, 112, 214),
-        14 => Color::new(41, 184, 219),
-        15 => Color::new(255, 255, 255),
-        // 216-color cube (16-231)
-        16..=231 => {
-            let n = n - 16;
-            let r = (n / 36) % 6;
-            let g = (n / 6) % 6;
-            let b = n % 6;
-            let to_255 = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
-            Color::new(to_255(r), to_255(g), to_255(b))
-        }
-        // Grayscale (232-255)
-        232..=255 => {
-            let gray = 8 + (n - 232) * 10;
-            Color::new(gray, gray, gray)
-        }
+/// Hyperlink of the cell under the pointer, if any
+fn link_at(
+    grid: &Grid,
+    point: ClientPoint,
+    rect: Option<PixelsRect>,
+    rows: usize,
+    cols: usize,
+) -> Option<Arc<Hyperlink>> {
+    let (row, col) = cell_at(point, rect?, rows, cols);
+    grid.get(row, col)?.link.clone()
+}
+
+/// Detected plain-text link under the pointer, if any
+fn detected_link_at(
+    detector: Option<&LinkDetector>,
+    grid: &Grid,
+    point: ClientPoint,
+    rect: Option<PixelsRect>,
+    rows: usize,
+    cols: usize,
+) -> Option<DetectedLink> {
+    let (row, col) = cell_at(point, rect?, rows, cols);
+    detector?.link_at(grid, row, col)
+}
+
+/// Copy text to the system clipboard through the webview
+fn write_clipboard(text: String) {
+    let eval = document::eval("navigator.clipboard.writeText(await dioxus.recv());");
+    let _ = eval.send(text);
+}
+
+/// Read the system clipboard through the webview
+async fn read_clipboard() -> Option<String> {
+    let mut eval = document::eval("dioxus.send(await navigator.clipboard.readText());");
+    eval.recv().await.ok()
+}
+
+/// Open a URI with the platform's default handler, if its scheme is allowed
+fn open_link(uri: &str) {
+    if openable_uri(uri) {
+        open_with_os(uri.as_ref());
     }
 }
 
-/// Scroll the grid up by one line
-fn scroll_up(grid: &mut Signal<Grid>, rows: usize, cols: usize) {
-    let mut g = grid.write();
-    // Move all rows up by one
-    for row in 1..rows {
-        for col in 0..cols {
-            if let Some(cell) = g.get(row, col).cloned() {
-                g.set(row - 1, col, cell);
-            }
-        }
+/// Whether a URI uses one of `OPEN_SCHEMES`
+fn openable_uri(uri: &str) -> bool {
+    uri.split_once(':').is_some_and(|(scheme, _)| {
+        OPEN_SCHEMES
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+    })
+}
+
+/// Absolute path for a detected path, or `None` if it can't be resolved
+///
+/// Relative paths are resolved against the shell's working directory `cwd`,
+/// and `~/` against `home`.
+fn resolve_path(path: &str, cwd: Option<&Path>, home: Option<&Path>) -> Option<PathBuf> {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return home.map(|home| home.join(rest));
     }
-    // Clear the last row
-    for col in 0..cols {
-        g.set(rows - 1, col, Cell::default());
+    let path = Path::new(path);
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        cwd.map(|cwd| cwd.join(path))
     }
 }
 
-/// Convert keyboard event to terminal input string
-fn key_to_string(evt: &KeyboardEvent) -> String {
-    let key = evt.key();
-
-    match key {
-        Key::Enter => "\r".to_string(),
-        Key::Backspace => "\x7f".to_string(),
-        Key::Tab => "\t".to_string(),
-        Key::Escape => "\x1b".to_string(),
-        Key::ArrowUp => "\x1b[A".to_string(),
-        Key::ArrowDown => "\x1b[B".to_string(),
-        Key::ArrowRight => "\x1b[C".to_string(),
-        Key::ArrowLeft => "\x1b[D".to_string(),
-        Key::Home => "\x1b[H".to_string(),
-        Key::End => "\x1b[F".to_string(),
-        Key::Delete => "\x1b[3~".to_string(),
-        Key::Character(c) => {
-            // Handle Ctrl+key combinations
-            if evt.modifiers().ctrl() && c.len() == 1 {
-                let ch = c.chars().next().unwrap();
-                if ch.is_ascii_lowercase() {
-                    // Ctrl+a = 0x01, Ctrl+b = 0x02, etc.
-                    let ctrl_char = (ch as u8 - b'a' + 1) as char;
-                    return ctrl_char.to_string();
-                }
-            }
-            c
-        }
-        _ => String::new(),
-    }
+/// Hand a URI or path to the platform's default handler
+///
+/// On Windows this goes through `url.dll` rather than `cmd /C start`, as `cmd`
+/// would re-parse `&`, `|` and `^` in the argument.
+fn open_with_os(target: &OsStr) {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let _ = command.arg(target).spawn();
 }
 
 #[cfg(test)]
@@ -554,10 +989,13 @@ mod tests {
 
     #[test]
     fn test_terminal_props_defaults() {
-        let props = TerminalProps {
+        // Event handlers need a scope to be created
+        let dom = VirtualDom::new(|| rsx! {});
+        let props = dom.in_scope(ScopeId::ROOT, || TerminalProps {
             command: "bash".to_string(),
             args: vec![],
             shell: String::new(),
+            shell_integration: false,
             rows: 24,
             cols: 120,
             font_size: 13,
@@ -565,8 +1003,23 @@ mod tests {
             theme: Theme::default(),
             background: None,
             foreground: None,
+            cursor_style: CursorStyle::default(),
+            on_title_change: EventHandler::default(),
+            set_window_title: false,
+            on_link_click: None,
+            link_detector: Some(LinkDetector::default()),
+            on_link_activate: None,
+            on_cwd_change: EventHandler::default(),
+            on_ready: EventHandler::default(),
+            on_notification: EventHandler::default(),
+            on_bell: EventHandler::default(),
+            visual_bell: false,
+            notify_command_after: None,
+            clipboard_policy: ClipboardPolicy::default(),
+            clipboard_limit: DEFAULT_CLIPBOARD_LIMIT,
+            image_memory_limit: DEFAULT_IMAGE_MEMORY_LIMIT,
             class: String::new(),
-        };
+        });
 
         assert_eq!(props.rows, 24);
         assert_eq!(props.cols, 120);
@@ -575,6 +1028,45 @@ mod tests {
     }
 
     #[test]
+    fn test_openable_uri() {
+        assert!(openable_uri("https://example.com/&calc"));
+        assert!(openable_uri("HTTP://example.com"));
+        assert!(openable_uri("file:///tmp/a.txt"));
+        assert!(openable_uri("mailto:someone@example.com"));
+        assert!(!openable_uri("javascript:alert(1)"));
+        assert!(!openable_uri("ssh://host"));
+        assert!(!openable_uri("no-scheme"));
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        let cwd = Path::new("/work/project");
+        let home = Path::new("/home/me");
+        assert_eq!(
+            resolve_path("src/main.rs", Some(cwd), Some(home)),
+            Some(PathBuf::from("/work/project/src/main.rs"))
+        );
+        assert_eq!(
+            resolve_path("/etc/hosts", None, None),
+            Some(PathBuf::from("/etc/hosts"))
+        );
+        assert_eq!(
+            resolve_path("~/notes.md", Some(cwd), Some(home)),
+            Some(PathBuf::from("/home/me/notes.md"))
+        );
+        // Without a known directory relative paths are dropped
+        assert_eq!(resolve_path("./a.rs", None, Some(home)), None);
+    }
+
+    #[test]
+    fn test_cell_size() {
+        use dioxus::html::geometry::euclid::{Point2D, Rect, Size2D};
+        let rect = Rect::new(Point2D::new(10.0, 20.0), Size2D::new(640.0, 384.0));
+        assert_eq!(cell_size(rect, 24, 80), (8, 16));
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn test_theme_override() {
         // Background/foreground props should override theme
         let theme = Theme::zinc();